@@ -1,23 +1,145 @@
+use clap::{Parser, Subcommand};
+use ledger::block::Genesis;
 use ledger::chain::Chain;
 use network::Network;
 use node::node::Node;
+use rpc::Rpc;
 use std::env;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use utils::keystore::{self, Keystore};
+use utils::Utils;
+
+#[derive(Parser)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the node, the default
+    Run,
+    /// Generate a node key pair
+    Keygen {
+        /// Write private.pem and public.pem to this directory instead of printing base64
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv::dotenv().ok();
 
-    let public_key = env::var("KEY_PUB").expect("KEY_PUB must be set");
-    let private_key = env::var("KEY_PRIV").expect("KEY_PRIV must be set");
+    match Cli::parse().command.unwrap_or(Command::Run) {
+        Command::Run => run().await,
+        Command::Keygen { out } => keygen(out),
+    }
+}
+
+async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let rpc_addr = env::var("RPC_ADDR").unwrap_or("0.0.0.0:8545".to_string());
+
+    let (network_tx, network_rx) = tokio::sync::mpsc::channel(100);
+    let (node_tx, node_rx) = tokio::sync::mpsc::channel(100);
 
-    let (network_tx, mut network_rx) = tokio::sync::mpsc::channel(100);
-    let (node_tx, mut node_rx) = tokio::sync::mpsc::channel(100);
+    // every node of a chain sets the same GENESIS_VALIDATOR and GOVERNANCE addresses
+    let genesis = Genesis {
+        validator: address_var("GENESIS_VALIDATOR")?.expect("GENESIS_VALIDATOR must be set"),
+        governance: address_var("GOVERNANCE")?,
+    };
+    let mut chain = Chain::new(genesis.clone());
+    chain.block_add(genesis.block())?;
+    // the key comes from an encrypted keystore, a PEM file in KEY_FILE or in plain text from KEY_PRIV
+    let node = if let Ok(path) = env::var("KEYSTORE") {
+        let keystore = Keystore::load(path)?;
+        let password_file = env::var("KEYSTORE_PASSWORD_FILE").ok().map(PathBuf::from);
+        let password = keystore::read_password(password_file.as_deref(), "Keystore password: ")?;
+        Node::with_keystore(&keystore, &password, chain, node_rx, network_tx)?
+    } else if let Ok(path) = env::var("KEY_FILE") {
+        let private_key = Utils::private_key_from_pem(&fs::read_to_string(path)?)?;
+        Node::new(private_key, chain, node_rx, network_tx)?
+    } else {
+        let private_key = env::var("KEY_PRIV").expect("KEY_PRIV, KEY_FILE or KEYSTORE must be set");
+        Node::new(private_key, chain, node_rx, network_tx)?
+    };
+    let mut network = Network::new(&node, node_tx.clone())?;
+    for (address, addr) in neighbors_var("NEIGHBORS")? {
+        network.neighbor_add(address, addr);
+    }
+    let rpc = Rpc::new(node.chain(), node_tx, node.events());
 
-    let chain = Chain::new();
-    let node = Node::new(public_key, private_key, chain, node_rx, network_tx)?;
-    let network = Network::new(node.id.clone(), network_rx, node_tx);
+    let rpc_addr = rpc_addr.parse()?;
+    // the first of them to fail stops the node with its error
+    tokio::try_join!(
+        async { node.run().await.map_err(Box::<dyn std::error::Error>::from) },
+        async {
+            network
+                .run(network_rx)
+                .await
+                .map_err(Box::<dyn std::error::Error>::from)
+        },
+        async {
+            rpc.run(rpc_addr)
+                .await
+                .map_err(Box::<dyn std::error::Error>::from)
+        },
+    )?;
 
-    let _ = tokio::join!(node.run(), network.run());
+    Ok(())
+}
+
+/// the address in the environment variable, none if it is not set
+/// a value that is not an address is an error, a node must not start on another chain
+fn address_var(name: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let Ok(address) = env::var(name) else {
+        return Ok(None);
+    };
+    Utils::decode_address(&address).map_err(|e| format!("invalid {} address: {}", name, e))?;
+    Ok(Some(address))
+}
+
+/// the neighbors in the environment variable, comma separated `address@host:port`
+fn neighbors_var(name: &str) -> Result<Vec<(String, SocketAddr)>, Box<dyn std::error::Error>> {
+    let Ok(neighbors) = env::var(name) else {
+        return Ok(vec![]);
+    };
+    let mut parsed = vec![];
+    for neighbor in neighbors.split(',').filter(|neighbor| !neighbor.is_empty()) {
+        let (address, addr) = neighbor
+            .split_once('@')
+            .ok_or_else(|| format!("invalid {} neighbor `{}`", name, neighbor))?;
+        Utils::decode_address(address).map_err(|e| format!("invalid {} address: {}", name, e))?;
+        let addr = addr
+            .parse()
+            .map_err(|e| format!("invalid {} neighbor `{}`: {}", name, neighbor, e))?;
+        parsed.push((address.to_string(), addr));
+    }
+    Ok(parsed)
+}
 
+fn keygen(out: Option<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+    let (private, public) = Utils::generate_keypair()?;
+    match out {
+        Some(dir) => {
+            fs::create_dir_all(&dir)?;
+            keystore::write_secret(
+                dir.join("private.pem"),
+                Utils::private_key_to_pem(&private)?,
+            )?;
+            fs::write(dir.join("public.pem"), Utils::public_key_to_pem(&public)?)?;
+            println!("Keys written to {}", dir.display());
+        }
+        None => {
+            println!("Private Key (Base64 Encoded):");
+            println!("{}", private);
+            println!("Public Key (Base64 Encoded):");
+            println!("{}", public);
+        }
+    }
+    println!("Address:");
+    println!("{}", Utils::address(&Utils::get_verifying_key(&public)?));
     Ok(())
 }
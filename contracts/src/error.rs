@@ -0,0 +1,11 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ContractError {
+    #[error("Invalid code: {0}")]
+    InvalidCode(String),
+    #[error("Out of gas, limit {0}")]
+    OutOfGas(u64),
+    #[error("Trapped: {0}")]
+    Trap(String),
+}
@@ -0,0 +1,393 @@
+//! a WebAssembly runtime for contracts, metered and deterministic
+//!
+//! a contract exports its `memory` and a `call` function with no parameters nor results,
+//! it reads its input and the chain through the host functions of the `env` module:
+//!
+//! - `input_size() -> i32`, `input_read(ptr)`: the call input
+//! - `output_write(ptr, len)`: the call output, the last write wins
+//! - `storage_read(key_ptr, key_len, value_ptr, value_cap) -> i32`: the value length,
+//!   -1 if there is none, copies at most `value_cap` bytes
+//! - `storage_write(key_ptr, key_len, value_ptr, value_len)`, `storage_remove(key_ptr, key_len)`
+//! - `caller(ptr) -> i32`: writes the caller address, at most `MAX_ADDRESS_SIZE` bytes, returns its length
+//! - `amount() -> i64`, `block_index() -> i64`, `block_timestamp() -> i64`
+//! - `transfer(receiver_ptr, receiver_len, amount) -> i32`: pays from the contract account, 0 if paid
+//!
+//! a trap fails the call, the host drops its changes
+
+pub mod error;
+
+use crate::error::ContractError;
+use wasmi::core::TrapCode;
+use wasmi::{
+    Caller, Config, Engine, Error, Extern, ExternType, Linker, Module, Store, StoreLimits,
+    StoreLimitsBuilder,
+};
+
+/// the maximum size of a module
+pub const MAX_CODE_SIZE: usize = 65_536;
+/// the maximum size of the linear memory of a contract
+pub const MAX_MEMORY_SIZE: usize = 1_048_576;
+pub const MAX_KEY_SIZE: usize = 64;
+pub const MAX_VALUE_SIZE: usize = 1_024;
+pub const MAX_OUTPUT_SIZE: usize = 1_024;
+pub const MAX_ADDRESS_SIZE: usize = 64;
+
+/// the gas of a host function call, on top of the instructions
+const GAS_HOST: u64 = 100;
+/// the gas per byte copied in or out of the contract memory
+const GAS_BYTE: u64 = 1;
+/// the gas per byte written to storage
+const GAS_STORAGE_BYTE: u64 = 100;
+
+const HOST_FUNCTIONS: [&str; 12] = [
+    "input_size",
+    "input_read",
+    "output_write",
+    "storage_read",
+    "storage_write",
+    "storage_remove",
+    "caller",
+    "amount",
+    "block_index",
+    "block_timestamp",
+    "transfer",
+    "abort",
+];
+
+/// the chain seen by a contract, changes are committed only if the call succeeds
+pub trait Host {
+    fn storage_get(&self, key: &[u8]) -> Option<Vec<u8>>;
+    /// remove the key if none
+    fn storage_set(&mut self, key: &[u8], value: Option<Vec<u8>>);
+    /// pay from the contract account
+    fn transfer(&mut self, receiver: &str, amount: u64) -> Result<(), String>;
+}
+
+/// the call a contract runs for
+pub struct Context {
+    pub caller: String,
+    /// the amount the caller paid the contract
+    pub amount: u64,
+    pub block_index: u64,
+    pub block_timestamp: u64,
+}
+
+pub struct Outcome {
+    pub gas_used: u64,
+    pub output: Vec<u8>,
+}
+
+struct Env<'a> {
+    host: &'a mut dyn Host,
+    context: &'a Context,
+    input: &'a [u8],
+    output: Vec<u8>,
+    limits: StoreLimits,
+}
+
+/// no floats, their NaN bits differ between machines, and fuel metering
+fn engine() -> Engine {
+    let mut config = Config::default();
+    config.floats(false).consume_fuel(true);
+    Engine::new(&config)
+}
+
+/// the module parses, imports host functions only and exports `call` and `memory`
+pub fn validate(code: &[u8]) -> Result<(), ContractError> {
+    module(&engine(), code).map(|_| ())
+}
+
+fn module(engine: &Engine, code: &[u8]) -> Result<Module, ContractError> {
+    if code.len() > MAX_CODE_SIZE {
+        return Err(ContractError::InvalidCode(format!(
+            "{} bytes, at most {}",
+            code.len(),
+            MAX_CODE_SIZE
+        )));
+    }
+    let module =
+        Module::new(engine, code).map_err(|e| ContractError::InvalidCode(e.to_string()))?;
+    for import in module.imports() {
+        if import.module() != "env" || !HOST_FUNCTIONS.contains(&import.name()) {
+            return Err(ContractError::InvalidCode(format!(
+                "unknown import {}.{}",
+                import.module(),
+                import.name()
+            )));
+        }
+    }
+    let mut call = false;
+    let mut memory = false;
+    for export in module.exports() {
+        match (export.name(), export.ty()) {
+            ("call", ExternType::Func(ty)) => {
+                call = ty.params().is_empty() && ty.results().is_empty()
+            }
+            ("memory", ExternType::Memory(_)) => memory = true,
+            _ => {}
+        }
+    }
+    if !call || !memory {
+        return Err(ContractError::InvalidCode(
+            "a contract exports memory and call".into(),
+        ));
+    }
+    Ok(module)
+}
+
+/// run the `call` export of the module with at most `gas`
+pub fn call(
+    code: &[u8],
+    host: &mut dyn Host,
+    context: &Context,
+    input: &[u8],
+    gas: u64,
+) -> Result<Outcome, ContractError> {
+    let engine = engine();
+    let module = module(&engine, code)?;
+    let env = Env {
+        host,
+        context,
+        input,
+        output: vec![],
+        limits: StoreLimitsBuilder::new()
+            .memory_size(MAX_MEMORY_SIZE)
+            .instances(1)
+            .build(),
+    };
+    let mut store = Store::new(&engine, env);
+    store.limiter(|env| &mut env.limits);
+    store.set_fuel(gas).map_err(|e| trap(e.into(), gas))?;
+
+    let linker = linker(&engine).map_err(|e| trap(e, gas))?;
+    let result = linker
+        .instantiate(&mut store, &module)
+        .and_then(|instance| instance.start(&mut store))
+        .and_then(|instance| instance.get_typed_func::<(), ()>(&store, "call"))
+        .and_then(|call| call.call(&mut store, ()));
+    result.map_err(|e| trap(e, gas))?;
+
+    let remaining = store.get_fuel().map_err(|e| trap(e.into(), gas))?;
+    Ok(Outcome {
+        gas_used: gas - remaining,
+        output: store.into_data().output,
+    })
+}
+
+fn trap(error: Error, gas: u64) -> ContractError {
+    match error.as_trap_code() {
+        Some(TrapCode::OutOfFuel) => ContractError::OutOfGas(gas),
+        _ => ContractError::Trap(error.to_string()),
+    }
+}
+
+fn linker<'a>(engine: &Engine) -> Result<Linker<Env<'a>>, Error> {
+    let mut linker = Linker::new(engine);
+    linker
+        .func_wrap("env", "input_size", |mut caller: Caller<Env>| {
+            charge(&mut caller, GAS_HOST)?;
+            Ok(caller.data().input.len() as i32)
+        })?
+        .func_wrap("env", "input_read", |mut caller: Caller<Env>, ptr: i32| {
+            let input = caller.data().input;
+            charge(&mut caller, GAS_HOST + GAS_BYTE * input.len() as u64)?;
+            memory_write(&mut caller, ptr, input)
+        })?
+        .func_wrap(
+            "env",
+            "output_write",
+            |mut caller: Caller<Env>, ptr: i32, len: i32| {
+                let output = memory_read(&mut caller, ptr, len, MAX_OUTPUT_SIZE)?;
+                caller.data_mut().output = output;
+                Ok(())
+            },
+        )?
+        .func_wrap(
+            "env",
+            "storage_read",
+            |mut caller: Caller<Env>, key_ptr: i32, key_len: i32, ptr: i32, cap: i32| {
+                let key = memory_read(&mut caller, key_ptr, key_len, MAX_KEY_SIZE)?;
+                let Some(value) = caller.data().host.storage_get(&key) else {
+                    return Ok(-1);
+                };
+                let len = value.len().min(cap.max(0) as usize);
+                charge(&mut caller, GAS_BYTE * len as u64)?;
+                memory_write(&mut caller, ptr, &value[..len])?;
+                Ok(value.len() as i32)
+            },
+        )?
+        .func_wrap(
+            "env",
+            "storage_write",
+            |mut caller: Caller<Env>, key_ptr: i32, key_len: i32, ptr: i32, len: i32| {
+                let key = memory_read(&mut caller, key_ptr, key_len, MAX_KEY_SIZE)?;
+                let value = memory_read(&mut caller, ptr, len, MAX_VALUE_SIZE)?;
+                charge(
+                    &mut caller,
+                    GAS_STORAGE_BYTE * (key.len() + value.len()) as u64,
+                )?;
+                caller.data_mut().host.storage_set(&key, Some(value));
+                Ok(())
+            },
+        )?
+        .func_wrap(
+            "env",
+            "storage_remove",
+            |mut caller: Caller<Env>, key_ptr: i32, key_len: i32| {
+                let key = memory_read(&mut caller, key_ptr, key_len, MAX_KEY_SIZE)?;
+                caller.data_mut().host.storage_set(&key, None);
+                Ok(())
+            },
+        )?
+        .func_wrap("env", "caller", |mut caller: Caller<Env>, ptr: i32| {
+            charge(&mut caller, GAS_HOST)?;
+            let address = caller.data().context.caller.clone();
+            memory_write(&mut caller, ptr, address.as_bytes())?;
+            Ok(address.len() as i32)
+        })?
+        .func_wrap("env", "amount", |mut caller: Caller<Env>| {
+            charge(&mut caller, GAS_HOST)?;
+            Ok(caller.data().context.amount as i64)
+        })?
+        .func_wrap("env", "block_index", |mut caller: Caller<Env>| {
+            charge(&mut caller, GAS_HOST)?;
+            Ok(caller.data().context.block_index as i64)
+        })?
+        .func_wrap("env", "block_timestamp", |mut caller: Caller<Env>| {
+            charge(&mut caller, GAS_HOST)?;
+            Ok(caller.data().context.block_timestamp as i64)
+        })?
+        .func_wrap(
+            "env",
+            "transfer",
+            |mut caller: Caller<Env>, ptr: i32, len: i32, amount: i64| {
+                let receiver = memory_read(&mut caller, ptr, len, MAX_ADDRESS_SIZE)?;
+                let receiver =
+                    String::from_utf8(receiver).map_err(|e| Error::new(e.to_string()))?;
+                let paid = caller.data_mut().host.transfer(&receiver, amount as u64);
+                Ok(if paid.is_ok() { 0 } else { 1 })
+            },
+        )?
+        .func_wrap("env", "abort", |_: Caller<Env>| -> Result<(), Error> {
+            Err(Error::new("aborted by the contract"))
+        })?;
+    Ok(linker)
+}
+
+fn charge(caller: &mut Caller<Env>, gas: u64) -> Result<(), Error> {
+    let fuel = caller.get_fuel()?;
+    if fuel < gas {
+        caller.set_fuel(0)?;
+        return Err(TrapCode::OutOfFuel.into());
+    }
+    caller.set_fuel(fuel - gas)?;
+    Ok(())
+}
+
+/// at most `limit` bytes at `ptr`, charged
+fn memory_read(
+    caller: &mut Caller<Env>,
+    ptr: i32,
+    len: i32,
+    limit: usize,
+) -> Result<Vec<u8>, Error> {
+    let len = usize::try_from(len).map_err(|_| Error::new("negative length"))?;
+    if len > limit {
+        return Err(Error::new(format!("{} bytes, at most {}", len, limit)));
+    }
+    charge(caller, GAS_HOST + GAS_BYTE * len as u64)?;
+    let mut buffer = vec![0; len];
+    memory(caller)?
+        .read(&*caller, ptr as u32 as usize, &mut buffer)
+        .map_err(|e| Error::new(e.to_string()))?;
+    Ok(buffer)
+}
+
+fn memory_write(caller: &mut Caller<Env>, ptr: i32, data: &[u8]) -> Result<(), Error> {
+    memory(caller)?
+        .write(&mut *caller, ptr as u32 as usize, data)
+        .map_err(|e| Error::new(e.to_string()))
+}
+
+fn memory(caller: &Caller<Env>) -> Result<wasmi::Memory, Error> {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| Error::new("no exported memory"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct TestHost {
+        storage: HashMap<Vec<u8>, Vec<u8>>,
+    }
+
+    impl Host for TestHost {
+        fn storage_get(&self, key: &[u8]) -> Option<Vec<u8>> {
+            self.storage.get(key).cloned()
+        }
+
+        fn storage_set(&mut self, key: &[u8], value: Option<Vec<u8>>) {
+            match value {
+                Some(value) => self.storage.insert(key.to_vec(), value),
+                None => self.storage.remove(key),
+            };
+        }
+
+        fn transfer(&mut self, _: &str, _: u64) -> Result<(), String> {
+            Err("no balance".into())
+        }
+    }
+
+    #[test]
+    fn test() {
+        let code = wat::parse_str(include_str!("../wat/counter.wat")).unwrap();
+        let context = Context {
+            caller: "caller".into(),
+            amount: 0,
+            block_index: 1,
+            block_timestamp: 0,
+        };
+        let mut host = TestHost::default();
+        assert!(validate(&code).is_ok());
+        for count in 1..=2u64 {
+            let outcome = call(&code, &mut host, &context, &[], 10_000).unwrap();
+            assert_eq!(outcome.output, count.to_le_bytes());
+            assert!(outcome.gas_used > 0);
+        }
+        // metering is deterministic
+        let first = call(&code, &mut host, &context, &[], 10_000).unwrap();
+        let second = call(&code, &mut host, &context, &[], 10_000).unwrap();
+        assert_eq!(first.gas_used, second.gas_used);
+        assert!(matches!(
+            call(&code, &mut host, &context, &[], first.gas_used - 1),
+            Err(ContractError::OutOfGas(_))
+        ));
+
+        let endless = wat::parse_str(
+            r#"(module (memory (export "memory") 1) (func (export "call") (loop $l (br $l))))"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            call(&endless, &mut host, &context, &[], 1_000_000),
+            Err(ContractError::OutOfGas(_))
+        ));
+
+        let floats = wat::parse_str(
+            r#"(module (memory (export "memory") 1)
+                (func (export "call") (drop (f64.add (f64.const 1) (f64.const 2)))))"#,
+        )
+        .unwrap();
+        assert!(validate(&floats).is_err());
+        let imports = wat::parse_str(
+            r#"(module (import "env" "random" (func)) (memory (export "memory") 1) (func (export "call")))"#,
+        )
+        .unwrap();
+        assert!(validate(&imports).is_err());
+        assert!(validate(b"not wasm").is_err());
+    }
+}
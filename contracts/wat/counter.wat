@@ -0,0 +1,12 @@
+;; counts its calls in the `count` key, outputs the count as 8 little endian bytes
+(module
+  (import "env" "storage_read" (func $storage_read (param i32 i32 i32 i32) (result i32)))
+  (import "env" "storage_write" (func $storage_write (param i32 i32 i32 i32)))
+  (import "env" "output_write" (func $output_write (param i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "count")
+  (func (export "call")
+    (drop (call $storage_read (i32.const 0) (i32.const 5) (i32.const 16) (i32.const 8)))
+    (i64.store (i32.const 16) (i64.add (i64.load (i32.const 16)) (i64.const 1)))
+    (call $storage_write (i32.const 0) (i32.const 5) (i32.const 16) (i32.const 8))
+    (call $output_write (i32.const 16) (i32.const 8))))
@@ -0,0 +1,80 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use ledger::block::{Block, Genesis};
+use ledger::chain::Chain;
+use ledger::transaction::Transaction;
+use utils::{SigningMode, Utils};
+
+const KEY_PRIV: &str = "MIGEAgEAMBAGByqGSM49AgEGBSuBBAAKBG0wawIBAQQgGYFjSRDEGRmqvaJreuMY22pZz3TojuOm2dEmxhtbPTyhRANCAARSnspJBeKF9TrV5WmDTsJXb/wtihZ4YyXRmGASMIbzdYuW+B5vh1B/dRvZ15Ne8ehUQ/xH023fVx1STJzkSeoS";
+const ADDRESS: &str = "crt1rda9nkk7dv63muvhry040whcfaed5ccygdyzfr";
+const RECEIVER: &str = "crt1az98ashxv3pa5x68yrz6ve8vz0ylaey28ccmy7";
+const TRANSACTIONS: usize = 1_000;
+
+/// a block of zero amount transfers from distinct senders, valid on top of the genesis block
+fn block() -> Block {
+    let signing_key = Utils::get_signing_key(KEY_PRIV).unwrap();
+    let transactions = (0..TRANSACTIONS)
+        .map(|_| {
+            let (private, public) = Utils::generate_keypair().unwrap();
+            let mut transaction = Transaction::new(1, 0, &public, RECEIVER, None).unwrap();
+            transaction
+                .sign_with(
+                    &Utils::get_signing_key(&private).unwrap(),
+                    SigningMode::Deterministic,
+                )
+                .unwrap();
+            transaction
+        })
+        .collect();
+    let mut block = Block::new(
+        1,
+        0,
+        Genesis::new(ADDRESS).block().hash,
+        ADDRESS.into(),
+        transactions,
+    );
+    block.sign(&signing_key).unwrap();
+    block
+}
+
+fn chain() -> Chain {
+    let genesis = Genesis::new(ADDRESS);
+    let mut chain = Chain::new(genesis.clone());
+    chain.block_add(genesis.block()).unwrap();
+    chain
+}
+
+fn verify(c: &mut Criterion) {
+    let block = block();
+
+    let mut group = c.benchmark_group("verify");
+    group.sample_size(10);
+    group.bench_function("sequential", |b| {
+        b.iter(|| assert!(block.transactions.iter().all(Transaction::verify)))
+    });
+    group.bench_function("parallel", |b| b.iter(|| block.verify().unwrap()));
+    group.bench_function("block_add", |b| {
+        b.iter_batched(
+            || (chain(), block.clone()),
+            |(mut chain, block)| chain.block_add(block).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+    // the signatures were verified when the transactions entered the mempool
+    group.bench_function("block_add_from_mempool", |b| {
+        b.iter_batched(
+            || {
+                let mut chain = chain();
+                for transaction in &block.transactions {
+                    chain.transaction_add(transaction.clone()).unwrap();
+                }
+                (chain, block.clone())
+            },
+            |(mut chain, block)| chain.block_add(block).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, verify);
+criterion_main!(benches);
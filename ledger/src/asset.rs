@@ -0,0 +1,188 @@
+use crate::error::LedgerError;
+use crate::executor::{operation_check, Executor};
+use crate::state::Transition;
+use crate::transaction::Transaction;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// the maximum length of an asset symbol
+pub const MAX_SYMBOL_LENGTH: usize = 12;
+/// the maximum number of decimals of an asset
+pub const MAX_DECIMALS: u8 = 18;
+
+/// a token issued on the ledger, identified by the hash of the transaction that issued it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Asset {
+    pub symbol: String,
+    /// Amounts are in units of 10^-decimals of the asset, display only
+    pub decimals: u8,
+    /// The address that may mint and burn the asset
+    pub issuer: String,
+    /// The amount minted and not burnt
+    pub supply: u64,
+}
+
+/// an operation on a user issued asset, in place of a transfer
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AssetOperation {
+    /// create an asset with no supply, issued by the sender
+    Issue { symbol: String, decimals: u8 },
+    /// credit the issuer with new units of the asset
+    Mint { asset: String, amount: u64 },
+    /// destroy units of the asset held by the issuer
+    Burn { asset: String, amount: u64 },
+}
+
+impl AssetOperation {
+    /// the asset the operation changes, none for an issue as the asset does not exist yet
+    pub fn asset(&self) -> Option<&str> {
+        match self {
+            AssetOperation::Issue { .. } => None,
+            AssetOperation::Mint { asset, .. } | AssetOperation::Burn { asset, .. } => Some(asset),
+        }
+    }
+}
+
+impl Executor for AssetOperation {
+    /// the symbol is 1 to `MAX_SYMBOL_LENGTH` uppercase letters and digits
+    fn verify(&self, transaction: &Transaction) -> Result<(), LedgerError> {
+        operation_check(transaction)?;
+        if let AssetOperation::Issue { symbol, decimals } = self {
+            if symbol.is_empty()
+                || symbol.len() > MAX_SYMBOL_LENGTH
+                || !symbol
+                    .bytes()
+                    .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+            {
+                return Err(LedgerError::InvalidAsset(format!(
+                    "invalid symbol {}",
+                    symbol
+                )));
+            }
+            if *decimals > MAX_DECIMALS {
+                return Err(LedgerError::InvalidAsset(format!(
+                    "{} decimals, at most {}",
+                    decimals, MAX_DECIMALS
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&self, state: &mut Transition, transaction: &Transaction) -> Result<(), LedgerError> {
+        let sender = &transaction.sender;
+        match self {
+            AssetOperation::Issue { symbol, decimals } => {
+                let asset = Asset {
+                    symbol: symbol.clone(),
+                    decimals: *decimals,
+                    issuer: sender.clone(),
+                    supply: 0,
+                };
+                state.asset_set(&transaction.hash, Some(asset));
+            }
+            AssetOperation::Mint { asset: id, amount } => {
+                let mut asset = issued(state, id, sender)?;
+                asset.supply = supply_add(id, asset.supply, *amount)?;
+                state.credit(sender, &Some(id.clone()), *amount)?;
+                state.asset_set(id, Some(asset));
+            }
+            AssetOperation::Burn { asset: id, amount } => {
+                let mut asset = issued(state, id, sender)?;
+                state.debit(sender, &Some(id.clone()), *amount)?;
+                asset.supply = supply_sub(id, asset.supply, *amount)?;
+                state.asset_set(id, Some(asset));
+            }
+        }
+        Ok(())
+    }
+
+    fn revert(&self, state: &mut Transition, transaction: &Transaction) -> Result<(), LedgerError> {
+        let sender = &transaction.sender;
+        match self {
+            AssetOperation::Issue { .. } => state.asset_set(&transaction.hash, None),
+            AssetOperation::Mint { asset: id, amount } => {
+                let mut asset = state.asset_get(id)?;
+                state.debit(sender, &Some(id.clone()), *amount)?;
+                asset.supply = supply_sub(id, asset.supply, *amount)?;
+                state.asset_set(id, Some(asset));
+            }
+            AssetOperation::Burn { asset: id, amount } => {
+                let mut asset = state.asset_get(id)?;
+                asset.supply = supply_add(id, asset.supply, *amount)?;
+                state.credit(sender, &Some(id.clone()), *amount)?;
+                state.asset_set(id, Some(asset));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// the asset, if the sender issued it
+fn issued(state: &Transition, id: &str, sender: &str) -> Result<Asset, LedgerError> {
+    let asset = state.asset_get(id)?;
+    if asset.issuer != sender {
+        return Err(LedgerError::NotIssuer {
+            asset: id.to_string(),
+            sender: sender.to_string(),
+        });
+    }
+    Ok(asset)
+}
+
+fn supply_add(id: &str, supply: u64, amount: u64) -> Result<u64, LedgerError> {
+    supply
+        .checked_add(amount)
+        .ok_or_else(|| LedgerError::BalanceOverflow {
+            address: id.to_string(),
+            balance: supply,
+            amount,
+        })
+}
+
+fn supply_sub(id: &str, supply: u64, amount: u64) -> Result<u64, LedgerError> {
+    supply
+        .checked_sub(amount)
+        .ok_or_else(|| LedgerError::InsufficientBalance {
+            sender: id.to_string(),
+            balance: supply,
+            amount,
+        })
+}
+
+impl fmt::Display for AssetOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetOperation::Issue { symbol, decimals } => {
+                write!(f, "issue:{}:{}", symbol, decimals)
+            }
+            AssetOperation::Mint { asset, amount } => write!(f, "mint:{}:{}", asset, amount),
+            AssetOperation::Burn { asset, amount } => write!(f, "burn:{}:{}", asset, amount),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_PUB: &str = "MFYwEAYHKoZIzj0CAQYFK4EEAAoDQgAEUp7KSQXihfU61eVpg07CV2/8LYoWeGMl0ZhgEjCG83WLlvgeb4dQf3Ub2deTXvHoVEP8R9Nt31cdUkyc5EnqEg==";
+
+    #[test]
+    fn test() {
+        let issue = |symbol: &str, decimals| {
+            let operation = AssetOperation::Issue {
+                symbol: symbol.into(),
+                decimals,
+            };
+            Transaction::new_asset(1, KEY_PUB, operation)
+        };
+        assert!(issue("USD", 2).is_ok());
+        assert!(issue("EUR2024", MAX_DECIMALS).is_ok());
+        for symbol in ["", "usd", "US-D", "ABCDEFGHIJKLM"] {
+            assert!(issue(symbol, 2).is_err(), "{}", symbol);
+        }
+        assert!(issue("USD", MAX_DECIMALS + 1).is_err());
+    }
+}
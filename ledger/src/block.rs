@@ -1,14 +1,30 @@
+use crate::contract::MAX_BLOCK_GAS;
+use crate::error::LedgerError;
 use crate::transaction::Transaction;
+use k256::ecdsa::{SigningKey, VerifyingKey};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use utils::Utils;
+use std::collections::HashSet;
+use utils::{SigningMode, Utils};
 
-#[derive(Serialize, Deserialize)]
+/// the maximum number of transactions in a block
+pub const MAX_BLOCK_TRANSACTIONS: usize = 1_000;
+/// the maximum size of a block in bytes, see `Block::size`
+pub const MAX_BLOCK_SIZE: usize = 1_048_576;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Block {
     pub index: u64,
     pub transactions: Vec<Transaction>,
     pub timestamp: u64,
     pub hash: String,
     pub prev_hash: String,
+    /// The producer node address
+    pub producer: String,
+    /// The producer public key, set when signing, it must hash to the producer address
+    producer_key: Option<VerifyingKey>,
+    /// The hex DER signature of the hash, decoded when verified
+    signature: Option<String>,
 }
 
 impl Block {
@@ -16,55 +32,349 @@ impl Block {
         index: u64,
         timestamp: u64,
         prev_hash: String,
+        producer: String,
         transactions: Vec<Transaction>,
     ) -> Block {
-        let hash = Block::calculate_hash(index, timestamp, &prev_hash, &transactions);
+        let hashes = transactions
+            .iter()
+            .map(|transaction| transaction.hash.as_str());
+        let hash = Block::calculate_hash(index, timestamp, &prev_hash, &producer, hashes);
         Block {
             index,
             transactions,
             timestamp,
             hash,
             prev_hash,
+            producer,
+            producer_key: None,
+            signature: None,
         }
     }
 
-    pub fn genesis() -> Block {
-        Block::new(0, 0, "0".to_string(), vec![])
+    /// sign the block hash by the producer
+    pub fn sign(&mut self, signing_key: &SigningKey) -> Result<(), LedgerError> {
+        self.sign_with(signing_key, SigningMode::Randomized)
     }
 
-    pub fn verify(&self) -> bool {
-        self.verify_hash() && self.verify_transactions()
+    pub fn sign_with(
+        &mut self,
+        signing_key: &SigningKey,
+        mode: SigningMode,
+    ) -> Result<(), LedgerError> {
+        if self.signature.is_some() {
+            return Err(LedgerError::AlreadySigned);
+        }
+        let producer_key = *signing_key.verifying_key();
+        if Utils::address(&producer_key) != self.producer {
+            return Err(LedgerError::InvalidKey(format!(
+                "the signing key is not the key of producer {}",
+                self.producer
+            )));
+        }
+        self.producer_key = Some(producer_key);
+        let sig = Utils::sign_data_with(&self.hash, signing_key, mode);
+        self.signature = Some(Utils::encode_signature(&sig));
+        Ok(())
     }
 
-    fn verify_hash(&self) -> bool {
-        self.hash
-            == Block::calculate_hash(
-                self.index,
-                self.timestamp,
-                &self.prev_hash,
-                &self.transactions,
-            )
+    /// the number of bytes the block occupies
+    pub fn size(&self) -> usize {
+        let header = 8
+            + 8
+            + self.hash.len()
+            + self.prev_hash.len()
+            + self.producer.len()
+            + self.producer_key.map_or(0, |_| 33)
+            + self.signature.as_ref().map_or(0, String::len);
+        header
+            + self
+                .transactions
+                .iter()
+                .map(|transaction| transaction.size())
+                .sum::<usize>()
+    }
+
+    /// the sum of the gas the calls of the block may use
+    pub fn gas(&self) -> u64 {
+        self.transactions.iter().fold(0u64, |gas, transaction| {
+            gas.saturating_add(transaction.gas())
+        })
+    }
+
+    /// verify everything that does not depend on the chain
+    /// the genesis block is not signed
+    pub fn verify(&self) -> Result<(), LedgerError> {
+        self.verify_with(|_| false)
     }
 
-    fn verify_transactions(&self) -> bool {
+    /// verify, trusting the signatures of the transactions `verified` accepts
+    pub(crate) fn verify_with(
+        &self,
+        verified: impl Fn(&Transaction) -> bool + Sync,
+    ) -> Result<(), LedgerError> {
+        self.verify_hash()?;
+        if self.index != 0 {
+            self.verify_signature()?;
+        }
+        self.verify_size()?;
+        self.verify_duplicates()?;
+        self.verify_transactions(verified)
+    }
+
+    fn verify_hash(&self) -> Result<(), LedgerError> {
+        let hash = Block::calculate_hash(
+            self.index,
+            self.timestamp,
+            &self.prev_hash,
+            &self.producer,
+            self.transactions
+                .iter()
+                .map(|transaction| transaction.hash.as_str()),
+        );
+        if self.hash != hash {
+            return Err(LedgerError::InvalidBlockHash { index: self.index });
+        }
+        Ok(())
+    }
+
+    fn verify_signature(&self) -> Result<(), LedgerError> {
+        let (signature, producer_key) = match (&self.signature, &self.producer_key) {
+            (Some(signature), Some(producer_key)) => (signature, producer_key),
+            _ => return Err(LedgerError::MissingBlockSignature { index: self.index }),
+        };
+        let signature = Utils::decode_signature(signature)
+            .map_err(|_| LedgerError::InvalidBlockSignature { index: self.index })?;
+        if Utils::address(producer_key) != self.producer
+            || !Utils::verify_signature(&self.hash, &signature, producer_key)
+        {
+            return Err(LedgerError::InvalidBlockSignature { index: self.index });
+        }
+        Ok(())
+    }
+
+    fn verify_size(&self) -> Result<(), LedgerError> {
+        if self.transactions.len() > MAX_BLOCK_TRANSACTIONS {
+            return Err(LedgerError::TooManyTransactions {
+                index: self.index,
+                count: self.transactions.len(),
+                limit: MAX_BLOCK_TRANSACTIONS,
+            });
+        }
+        let size = self.size();
+        if size > MAX_BLOCK_SIZE {
+            return Err(LedgerError::BlockTooLarge {
+                index: self.index,
+                size,
+                limit: MAX_BLOCK_SIZE,
+            });
+        }
+        let gas = self.gas();
+        if gas > MAX_BLOCK_GAS {
+            return Err(LedgerError::BlockGasExceeded {
+                index: self.index,
+                gas,
+                limit: MAX_BLOCK_GAS,
+            });
+        }
+        Ok(())
+    }
+
+    fn verify_duplicates(&self) -> Result<(), LedgerError> {
+        let mut seen = HashSet::new();
         for transaction in &self.transactions {
-            if !transaction.verify() {
-                return false;
+            if !seen.insert(&transaction.hash) {
+                return Err(LedgerError::DuplicateTransaction {
+                    index: self.index,
+                    hash: transaction.hash.clone(),
+                });
             }
         }
-        true
+        Ok(())
     }
 
-    fn calculate_hash(
+    /// the signatures are checked in parallel, the first invalid transaction is reported
+    fn verify_transactions(
+        &self,
+        verified: impl Fn(&Transaction) -> bool + Sync,
+    ) -> Result<(), LedgerError> {
+        let invalid = self.transactions.par_iter().find_first(|transaction| {
+            !(transaction.verify_except_signature()
+                && (verified(transaction) || transaction.verify_signature()))
+        });
+        match invalid {
+            Some(transaction) => Err(LedgerError::InvalidTransaction {
+                sender: transaction.sender.clone(),
+                nonce: transaction.nonce,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// the hash of the canonical JSON of the header fields, every field delimited
+    /// so no two headers share a preimage
+    fn calculate_hash<'a>(
         index: u64,
         timestamp: u64,
-        prev_hash: &str,
-        transactions: &Vec<Transaction>,
+        prev_hash: &'a str,
+        producer: &'a str,
+        transactions: impl Iterator<Item = &'a str>,
     ) -> String {
-        let mut data = format!("{}{}{}", index, timestamp, prev_hash);
-        for transaction in transactions {
-            data.push_str(&transaction.hash);
-        }
+        let preimage = Preimage {
+            index,
+            timestamp,
+            prev_hash,
+            producer,
+            transactions: transactions.collect(),
+        };
+        let data = serde_json::to_string(&preimage).expect("the fields serialize");
         Utils::hash_data(&data)
     }
+
+    /// the signed header, none for the genesis block
+    pub fn header(&self) -> Option<BlockHeader> {
+        let (signature, producer_key) = match (&self.signature, self.producer_key) {
+            (Some(signature), Some(producer_key)) => (signature.clone(), producer_key),
+            _ => return None,
+        };
+        Some(BlockHeader {
+            index: self.index,
+            timestamp: self.timestamp,
+            prev_hash: self.prev_hash.clone(),
+            producer: self.producer.clone(),
+            transactions: self
+                .transactions
+                .iter()
+                .map(|transaction| transaction.hash.clone())
+                .collect(),
+            producer_key,
+            signature,
+        })
+    }
+}
+
+/// what a block hash covers
+#[derive(Serialize)]
+struct Preimage<'a> {
+    index: u64,
+    timestamp: u64,
+    prev_hash: &'a str,
+    producer: &'a str,
+    transactions: Vec<&'a str>,
+}
+
+/// the parameters every node of a chain starts from
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Genesis {
+    /// The address that produces the blocks while no validator has bonded stake
+    pub validator: String,
+    /// The address allowed to send governance transactions, no governance if none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub governance: Option<String>,
+}
+
+impl Genesis {
+    pub fn new(validator: &str) -> Genesis {
+        Genesis {
+            validator: validator.to_string(),
+            governance: None,
+        }
+    }
+
+    /// the first block, its producer is the validator and its prev_hash commits to the parameters
+    /// the genesis block is not signed
+    pub fn block(&self) -> Block {
+        let parameters = serde_json::to_string(self).expect("the parameters serialize");
+        let prev_hash = Utils::hash_data(&parameters);
+        Block::new(0, 0, prev_hash, self.validator.clone(), vec![])
+    }
+}
+
+/// a signed block without its transactions, enough to show what its producer signed
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub index: u64,
+    pub timestamp: u64,
+    pub prev_hash: String,
+    pub producer: String,
+    /// The hashes of the transactions, in order
+    pub transactions: Vec<String>,
+    pub producer_key: VerifyingKey,
+    /// The hex DER signature of the block hash
+    pub signature: String,
+}
+
+impl BlockHeader {
+    /// the hash of the block
+    pub fn hash(&self) -> String {
+        Block::calculate_hash(
+            self.index,
+            self.timestamp,
+            &self.prev_hash,
+            &self.producer,
+            self.transactions.iter().map(String::as_str),
+        )
+    }
+
+    /// the producer key hashes to the producer address and signed the hash
+    pub fn verify(&self) -> Result<(), LedgerError> {
+        let signature = Utils::decode_signature(&self.signature)
+            .map_err(|_| LedgerError::InvalidBlockSignature { index: self.index })?;
+        if Utils::address(&self.producer_key) != self.producer
+            || !Utils::verify_signature(&self.hash(), &signature, &self.producer_key)
+        {
+            return Err(LedgerError::InvalidBlockSignature { index: self.index });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixture() {
+        let producer_key = "MIGEAgEAMBAGByqGSM49AgEGBSuBBAAKBG0wawIBAQQgGYFjSRDEGRmqvaJreuMY22pZz3TojuOm2dEmxhtbPTyhRANCAARSnspJBeKF9TrV5WmDTsJXb/wtihZ4YyXRmGASMIbzdYuW+B5vh1B/dRvZ15Ne8ehUQ/xH023fVx1STJzkSeoS";
+        let producer = "crt1rda9nkk7dv63muvhry040whcfaed5ccygdyzfr";
+        let hash = "edbb85ade29e24d03c39ed106cf0dfc95455603fde6c154ba88821d4fa69a466";
+        let signature = "3045022100bef85f088481fccc5bf9314c4bab63a400d6d6913c02e3b7bd6cd6c19e7834610220103ca3d69a11eba1b9cbd0d1a7a264dfdf464e7e4f1a42ab68e193b28a37b8ac";
+
+        let mut block = Block::new(
+            1,
+            0,
+            Block::new(0, 0, "0".into(), String::new(), vec![]).hash,
+            producer.into(),
+            vec![],
+        );
+        block
+            .sign_with(
+                &Utils::get_signing_key(producer_key).unwrap(),
+                SigningMode::Deterministic,
+            )
+            .unwrap();
+        assert_eq!(block.hash, hash);
+        assert_eq!(block.signature.as_deref(), Some(signature));
+        assert!(block.verify().is_ok());
+        assert_eq!(block.header().unwrap().signature, signature);
+
+        // the signature read back is the one verified
+        let mut json = serde_json::to_value(&block).unwrap();
+        json["signature"] = signature.replace("3045", "3044").into();
+        let tampered: Block = serde_json::from_value(json).unwrap();
+        assert!(matches!(
+            tampered.verify(),
+            Err(LedgerError::InvalidBlockSignature { index: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_hash_fields() {
+        // the same digits split between the index and the timestamp
+        let first = Block::new(1, 23, "0".into(), String::new(), vec![]);
+        let second = Block::new(12, 3, "0".into(), String::new(), vec![]);
+        assert_ne!(first.hash, second.hash);
+        let first = Block::new(1, 0, "ab".into(), "c".into(), vec![]);
+        let second = Block::new(1, 0, "a".into(), "bc".into(), vec![]);
+        assert_ne!(first.hash, second.hash);
+    }
 }
@@ -0,0 +1,37 @@
+use crate::transaction::Transaction;
+use std::collections::HashMap;
+
+/// the signatures already verified, by transaction hash
+/// a hit needs the same signature, a copy with another signature is verified again
+pub(crate) struct SignatureCache {
+    signatures: HashMap<String, String>,
+}
+
+impl SignatureCache {
+    pub(crate) fn new() -> SignatureCache {
+        SignatureCache {
+            signatures: HashMap::new(),
+        }
+    }
+
+    /// record the signature of a verified transaction
+    pub(crate) fn insert(&mut self, transaction: &Transaction) {
+        if let Some(signatures) = transaction.signatures_encoded() {
+            self.signatures.insert(transaction.hash.clone(), signatures);
+        }
+    }
+
+    pub(crate) fn contains(&self, transaction: &Transaction) -> bool {
+        match (
+            self.signatures.get(&transaction.hash),
+            transaction.signatures_encoded(),
+        ) {
+            (Some(cached), Some(signatures)) => *cached == signatures,
+            _ => false,
+        }
+    }
+
+    pub(crate) fn remove(&mut self, hash: &str) {
+        self.signatures.remove(hash);
+    }
+}
@@ -1,24 +1,55 @@
-use crate::block::Block;
+use crate::asset::Asset;
+use crate::block::{Block, Genesis};
+use crate::cache::SignatureCache;
+use crate::contract::{Contract, Receipt, MAX_BLOCK_GAS};
+use crate::error::LedgerError;
+use crate::event::Event;
+use crate::governance::{self, Control};
+use crate::htlc::Htlc;
+use crate::index::{Index, TransactionLocation};
 use crate::mempool::Mempool;
+use crate::staking::{self, Equivocation, Stake, Unbonding, Validator};
+use crate::state::{BalanceKey, State, StateChanges, Transition};
 use crate::transaction::Transaction;
-use std::collections::HashMap;
+use k256::ecdsa::SigningKey;
+use std::collections::{BTreeMap, HashMap};
+
+/// how far in seconds a block timestamp may be ahead of the local clock
+pub const MAX_TIMESTAMP_DRIFT: u64 = 15;
+/// how far ahead of the chain a block is kept until its parent arrives
+pub const MAX_ORPHAN_BLOCKS: u64 = 64;
+/// how deep below the top a competing branch may fork, and how many of its blocks are kept
+pub const MAX_FORK_BLOCKS: u64 = 64;
 
 pub struct Chain {
     pub blocks: Vec<Block>,
-    block_orphan: Vec<Block>,
-    balance: HashMap<String, u64>,
-    nonce: HashMap<String, u64>,
+    /// blocks ahead of the chain by index, the first one received for each index
+    block_orphan: BTreeMap<u64, Block>,
+    /// blocks of competing branches by hash, until their branch is longer than the chain
+    block_fork: HashMap<String, Block>,
+    state: State,
     mempool: Mempool,
+    index: Index,
+    /// the mempool signatures, not verified again when their block is imported
+    signatures: SignatureCache,
+    /// state changes not yet taken by `events_drain`
+    events: Vec<Event>,
+    /// the parameters of the chain, only their genesis block is accepted
+    genesis: Genesis,
 }
 
 impl Chain {
-    pub fn new() -> Chain {
+    pub fn new(genesis: Genesis) -> Chain {
         Chain {
             blocks: vec![],
-            block_orphan: vec![],
-            balance: HashMap::new(),
-            nonce: HashMap::new(),
+            block_orphan: BTreeMap::new(),
+            block_fork: HashMap::new(),
+            state: State::default(),
             mempool: Mempool::new(),
+            index: Index::new(),
+            signatures: SignatureCache::new(),
+            events: vec![],
+            genesis,
         }
     }
 
@@ -32,7 +63,7 @@ impl Chain {
             if i != block.index as usize {
                 return false;
             }
-            if !block.verify() {
+            if block.verify().is_err() {
                 return false;
             }
             if let Some(prev_block) = prev_block {
@@ -46,166 +77,1846 @@ impl Chain {
         true
     }
 
-    pub fn transaction_add(
-        &mut self,
-        transaction: Transaction,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn transaction_add(&mut self, transaction: Transaction) -> Result<(), LedgerError> {
         if self.transaction_seen(&transaction.sender, transaction.nonce) {
-            return Err("Transaction already seen".into());
+            return Err(LedgerError::TransactionSeen {
+                sender: transaction.sender,
+                nonce: transaction.nonce,
+            });
+        }
+        // a second copy would share the signature cache entry and the events of the first
+        if self.mempool.contains(&transaction.hash)
+            || self.index.transaction(&transaction.hash).is_some()
+        {
+            return Err(LedgerError::TransactionKnown {
+                hash: transaction.hash,
+            });
         }
 
         if !transaction.verify() {
-            return Err("Invalid transaction".into());
+            return Err(LedgerError::InvalidTransaction {
+                sender: transaction.sender,
+                nonce: transaction.nonce,
+            });
         }
 
+        // time-locked transactions wait in the mempool, expired ones are refused
+        if let Some(last_block) = self.blocks.last() {
+            let timestamp = now().max(last_block.timestamp);
+            if let Err(e @ LedgerError::TransactionExpired { .. }) =
+                transaction.validity_check(last_block.index + 1, timestamp)
+            {
+                return Err(e);
+            }
+            self.control_check(&transaction, timestamp)?;
+        }
+
+        self.signatures.insert(&transaction);
+        self.events.push(Event::TransactionAdded {
+            hash: transaction.hash.clone(),
+            sender: transaction.sender.clone(),
+            receivers: transaction.receivers(),
+        });
         self.mempool.push(transaction);
 
         Ok(())
     }
 
-    fn transaction_balance_verify(&self, transaction: &Transaction) -> bool {
-        let sender_balance = self.balance.get(&transaction.sender).unwrap_or(&0);
-        if *sender_balance < transaction.amount {
-            return false;
+    /// refuse transactions of frozen accounts and transfers over the daily limit
+    /// of the sender, other spending is only counted when the block applies
+    fn control_check(&self, transaction: &Transaction, timestamp: u64) -> Result<(), LedgerError> {
+        let sender = &transaction.sender;
+        let control = self.control_get(sender);
+        if control.frozen && self.governance_get() != Some(sender.as_str()) {
+            return Err(LedgerError::AccountFrozen {
+                address: sender.clone(),
+            });
         }
-        true
-    }
-
-    fn transaction_nonce_verify(&self, transaction: &Transaction) -> bool {
-        let nonce = transaction.nonce;
-        let last_known_nonce = self.nonce.get(&transaction.sender).unwrap_or(&0);
-        if nonce != last_known_nonce + 1 {
-            return false;
+        if transaction.asset.is_none() {
+            let amount = transaction.total().unwrap_or(u64::MAX);
+            let spent = self.spending_get(sender, governance::day(timestamp));
+            governance::limit_check(sender, control.limit, spent, amount)?;
         }
-        true
+        Ok(())
     }
 
     fn transaction_seen(&self, sender: &str, nonce: u64) -> bool {
-        let last_known_nonce = *self.nonce.get(sender).unwrap_or(&0);
-        nonce < last_known_nonce
+        let last_known_nonce = *self.state.nonce.get(sender).unwrap_or(&0);
+        nonce <= last_known_nonce
+    }
+
+    /// validate the block and apply it on top of the chain, then the orphans following it
+    /// blocks from the future are kept as orphans
+    pub fn block_add(&mut self, block: Block) -> Result<(), LedgerError> {
+        self.block_push(block)?;
+        self.orphans_connect();
+        Ok(())
     }
 
-    pub fn block_add(&mut self, block: Block) -> Result<(), Box<dyn std::error::Error>> {
-        block.verify();
+    fn block_push(&mut self, block: Block) -> Result<(), LedgerError> {
+        block.verify_with(|transaction| self.signatures.contains(transaction))?;
 
-        if block.index == 0 {
-            self.blocks.push(Block::genesis());
-            return Ok(());
+        let top = match self.blocks.last() {
+            Some(last_block) => last_block.index,
+            None => {
+                if block.index != 0 {
+                    return Err(LedgerError::NoGenesis);
+                }
+                if block.hash != self.genesis.block().hash {
+                    return Err(LedgerError::InvalidGenesis);
+                }
+                self.state.governance = self.genesis.governance.clone();
+                self.blocks.push(block);
+                self.state.validators.insert(0, vec![]);
+                return Ok(());
+            }
+        };
+        if block.index <= top {
+            let known = &self.blocks[block.index as usize];
+            if block.index == 0
+                || known.hash == block.hash
+                || self.block_fork.contains_key(&block.hash)
+            {
+                return Err(LedgerError::BlockKnown { index: block.index });
+            }
+            self.equivocation_check(&block);
+        }
+
+        let now = now();
+        if block.timestamp > now + MAX_TIMESTAMP_DRIFT {
+            return Err(LedgerError::TimestampInFuture {
+                index: block.index,
+                timestamp: block.timestamp,
+                now,
+            });
+        }
+
+        if block.index <= top || self.block_fork.contains_key(&block.prev_hash) {
+            return self.fork_push(block);
         }
 
-        let last_block = self.blocks.last().unwrap();
+        let last_block = &self.blocks[top as usize];
         if last_block.index + 1 != block.index {
-            self.block_orphan.push(block);
+            if block.index - last_block.index <= MAX_ORPHAN_BLOCKS {
+                self.block_orphan.entry(block.index).or_insert(block);
+            }
             return Ok(());
         }
         if last_block.hash != block.prev_hash {
-            return Err("Invalid prev_hash".into());
+            return Err(LedgerError::InvalidPrevHash {
+                expected: last_block.hash.clone(),
+                got: block.prev_hash,
+            });
         }
+        if block.timestamp < last_block.timestamp {
+            return Err(LedgerError::TimestampNotMonotonic {
+                index: block.index,
+                timestamp: block.timestamp,
+                prev_timestamp: last_block.timestamp,
+            });
+        }
+        self.producer_check(&block)?;
 
+        // the transactions of the block apply one after the other
+        let mut transition = Transition::new(&self.state, block.index, block.timestamp);
         for transaction in &block.transactions {
-            if !transaction.verify() {
-                return Err(format!(
-                    "Invalid transaction\nsender:{} nonce:{}",
-                    transaction.sender, transaction.nonce
-                )
-                .into());
+            transaction.validity_check(block.index, block.timestamp)?;
+            transition.apply(transaction)?;
+        }
+        let changes = transition.finish();
+
+        self.block_apply(block, changes);
+
+        Ok(())
+    }
+
+    /// keep a block of a competing branch, and switch to the branch once it is longer than the chain
+    /// the blocks of the branch are only validated against the state when it is switched to
+    fn fork_push(&mut self, block: Block) -> Result<(), LedgerError> {
+        let top = self.blocks.len() as u64 - 1;
+        let on_chain = |index: u64, hash: &str| {
+            self.blocks
+                .get(index as usize)
+                .is_some_and(|block| block.hash == hash)
+        };
+        let parent_known = on_chain(block.index - 1, &block.prev_hash)
+            || self.block_fork.contains_key(&block.prev_hash);
+        if !parent_known || block.index + MAX_FORK_BLOCKS <= top {
+            return Err(LedgerError::BlockKnown { index: block.index });
+        }
+        if block.index <= top {
+            if self.block_fork.len() as u64 >= MAX_FORK_BLOCKS {
+                return Err(LedgerError::BlockKnown { index: block.index });
             }
+            self.block_fork.insert(block.hash.clone(), block);
+            return Ok(());
+        }
+
+        // the branch from the block back to the chain, the longer chain wins
+        let mut branch = vec![block];
+        while let Some(parent) = self.block_fork.remove(&branch[branch.len() - 1].prev_hash) {
+            branch.push(parent);
+        }
+        branch.reverse();
+        let reverted = self.reorg(branch)?;
+        for block in reverted {
+            self.block_fork.insert(block.hash.clone(), block);
+        }
+        let top = self.blocks.len() as u64 - 1;
+        self.block_fork
+            .retain(|_, block| block.index + MAX_FORK_BLOCKS > top);
+        Ok(())
+    }
 
-            let nonce = transaction.nonce;
-            let last_known_nonce = self.nonce.get(&transaction.sender).unwrap_or(&0);
-            if nonce != last_known_nonce + 1 {
-                return Err(format!(
-                    "Invalid nonce\nsender:{} nonce:{}",
-                    transaction.sender, nonce
-                )
-                .into());
+    /// apply the orphans that follow the top of the chain, the first invalid one is dropped
+    /// with those after it
+    fn orphans_connect(&mut self) {
+        let Some(last_block) = self.blocks.last() else {
+            return;
+        };
+        self.block_orphan = self.block_orphan.split_off(&(last_block.index + 1));
+        while let Some(block) = self.block_orphan.remove(&(self.blocks.len() as u64)) {
+            if self.block_push(block).is_err() {
+                self.block_orphan.clear();
+                return;
             }
+        }
+    }
 
-            let sender_balance = self.balance.get(&transaction.sender).unwrap_or(&0);
-            if *sender_balance < transaction.amount {
-                return Err(format!(
-                    "Insufficient balance\nsender:{} nonce:{}",
-                    transaction.sender, nonce
-                )
-                .into());
+    /// the addresses that may produce the block at the index, the validators of its
+    /// epoch with stake left, or the genesis validator while there are none
+    fn producers(&self, index: u64) -> Vec<String> {
+        let computed;
+        let validators = match self.state.validators.get(&staking::epoch(index)) {
+            Some(validators) => validators,
+            // the first block of the epoch, the set is taken when it is applied
+            None => {
+                computed = staking::validators(&self.state.stakes);
+                &computed
             }
+        };
+        let bonded: Vec<String> = validators
+            .iter()
+            .filter(|validator| {
+                let stake = self.state.stakes.get(&validator.address);
+                stake.is_some_and(|stake| stake.bonded > 0)
+            })
+            .map(|validator| validator.address.clone())
+            .collect();
+        if bonded.is_empty() {
+            return vec![self.genesis.validator.clone()];
+        }
+        bonded
+    }
+
+    /// a producer outside the producers of the block is refused
+    fn producer_check(&self, block: &Block) -> Result<(), LedgerError> {
+        if self.producers(block.index).contains(&block.producer) {
+            return Ok(());
+        }
+        Err(LedgerError::NotValidator {
+            index: block.index,
+            producer: block.producer.clone(),
+        })
+    }
+
+    /// the address whose turn it is to produce the next block, the producers take turns by index
+    pub fn producer_due(&self) -> Option<String> {
+        let index = self.blocks.last()?.index + 1;
+        let producers = self.producers(index);
+        Some(producers[(index % producers.len() as u64) as usize].clone())
+    }
+
+    /// a known height with another block by the same producer is evidence for slashing the producer
+    fn equivocation_check(&mut self, block: &Block) {
+        let Some(known) = self.blocks.get(block.index as usize) else {
+            return;
+        };
+        if known.producer != block.producer || known.hash == block.hash {
+            return;
         }
+        if let (Some(first), Some(second)) = (known.header(), block.header()) {
+            self.events.push(Event::Equivocation {
+                producer: block.producer.clone(),
+                index: block.index,
+                evidence: Box::new(Equivocation { first, second }),
+            });
+        }
+    }
 
+    /// apply a validated block and the state changes of its transactions on top of the chain
+    fn block_apply(&mut self, block: Block, changes: StateChanges) {
+        if block.index.is_multiple_of(staking::EPOCH_LENGTH) {
+            let validators = staking::validators(&self.state.stakes);
+            self.state
+                .validators
+                .insert(staking::epoch(block.index), validators);
+        }
+        let keys = changes.balance.keys().cloned().collect();
+        self.state.commit(changes);
         for transaction in &block.transactions {
-            let sender_balance = *self.balance.get(&transaction.sender).unwrap_or(&0);
-            let receiver_balance = *self.balance.get(&transaction.receiver).unwrap_or(&0);
-            self.balance.insert(
-                transaction.sender.clone(),
-                sender_balance - transaction.amount,
-            );
-            self.balance.insert(
-                transaction.receiver.clone(),
-                receiver_balance + transaction.amount,
-            );
+            self.signatures.remove(&transaction.hash);
+            if self.mempool.remove(&transaction.hash).is_some() {
+                self.events
+                    .push(Event::transaction_removed(transaction, true));
+            }
+        }
+        self.index.block_add(&block);
+        self.events.push(Event::BlockApplied {
+            index: block.index,
+            hash: block.hash.clone(),
+        });
+        self.balance_events(keys);
+        // the next block is higher and not older
+        for transaction in self.mempool.expire(block.index + 1, block.timestamp) {
+            self.signatures.remove(&transaction.hash);
+            self.events
+                .push(Event::transaction_removed(&transaction, false));
         }
 
         self.blocks.push(block);
+    }
 
-        Ok(())
+    /// remove the top block and undo its state changes
+    /// the genesis block can not be reverted
+    pub fn block_revert(&mut self) -> Result<Block, LedgerError> {
+        if self.blocks.len() <= 1 {
+            return Err(LedgerError::NoGenesis);
+        }
+        let block = self.blocks.last().ok_or(LedgerError::NoGenesis)?;
+        let mut transition = Transition::new(&self.state, block.index, block.timestamp);
+        for transaction in block.transactions.iter().rev() {
+            transition.revert(transaction)?;
+        }
+        let changes = transition.finish();
+        let block = self.blocks.pop().ok_or(LedgerError::NoGenesis)?;
+        self.index.block_remove(&block);
+        if block.index.is_multiple_of(staking::EPOCH_LENGTH) {
+            self.state.validators.remove(&staking::epoch(block.index));
+        }
+        let keys = changes.balance.keys().cloned().collect();
+        self.state.commit(changes);
+        self.events.push(Event::BlockReverted {
+            index: block.index,
+            hash: block.hash.clone(),
+        });
+        self.balance_events(keys);
+
+        Ok(block)
+    }
+
+    /// record the balances a block changed, by account and asset
+    fn balance_events(&mut self, mut keys: Vec<BalanceKey>) {
+        keys.sort();
+        for key in keys {
+            let balance = *self.state.balance.get(&key).unwrap_or(&0);
+            let (address, asset) = key;
+            self.events.push(Event::BalanceChanged {
+                address,
+                asset,
+                balance,
+            });
+        }
     }
 
-    pub fn block_mint(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    /// take the state changes recorded since the last call
+    /// whoever owns the chain should drain them regularly
+    pub fn events_drain(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// switch to a competing branch, `blocks` are consecutive and the first one
+    /// follows a block of this chain
+    /// the branch must be longer than the blocks it replaces
+    /// on failure the chain is left as it was
+    /// the transactions of the reverted blocks go back to the mempool
+    /// returns the reverted blocks
+    pub fn reorg(&mut self, blocks: Vec<Block>) -> Result<Vec<Block>, LedgerError> {
+        let fork = match blocks.first() {
+            Some(block) if block.index > 0 && block.index as usize <= self.blocks.len() => {
+                block.index as usize
+            }
+            _ => return Err(LedgerError::InvalidBranch),
+        };
+        if blocks
+            .iter()
+            .enumerate()
+            .any(|(i, block)| block.index as usize != fork + i)
+        {
+            return Err(LedgerError::InvalidBranch);
+        }
+        if fork + blocks.len() <= self.blocks.len() {
+            return Err(LedgerError::BranchNotLonger {
+                length: fork + blocks.len(),
+                current: self.blocks.len(),
+            });
+        }
+
+        let mut reverted = vec![];
+        while self.blocks.len() > fork {
+            reverted.push(self.block_revert()?);
+        }
+        reverted.reverse();
+
+        for block in blocks {
+            if let Err(e) = self.block_push(block) {
+                while self.blocks.len() > fork {
+                    self.block_revert()?;
+                }
+                // the state is back to the one the reverted blocks applied on
+                for block in reverted {
+                    let mut transition = Transition::new(&self.state, block.index, block.timestamp);
+                    for transaction in &block.transactions {
+                        transition.apply(transaction)?;
+                    }
+                    let changes = transition.finish();
+                    self.block_apply(block, changes);
+                }
+                return Err(e);
+            }
+        }
+
+        // the transactions the branch does not include wait for another block
+        for block in &reverted {
+            for transaction in &block.transactions {
+                let _ = self.transaction_add(transaction.clone());
+            }
+        }
+        Ok(reverted)
+    }
+
+    /// find a transaction by its hash
+    pub fn transaction_get(&self, hash: &str) -> Option<(TransactionLocation, &Transaction)> {
+        let location = self.index.transaction(hash)?;
+        Some((location, self.transaction_at(location)))
+    }
+
+    /// the transactions sent or received by the address, oldest first
+    pub fn account_history(&self, address: &str) -> Vec<(TransactionLocation, &Transaction)> {
+        self.index
+            .account(address)
+            .iter()
+            .map(|location| (*location, self.transaction_at(*location)))
+            .collect()
+    }
+
+    fn transaction_at(&self, location: TransactionLocation) -> &Transaction {
+        &self.blocks[location.block as usize].transactions[location.position]
+    }
+
+    /// build a block from the mempool, signed by the producer
+    pub fn block_mint(
+        &mut self,
+        producer: &str,
+        signing_key: &SigningKey,
+    ) -> Result<(), LedgerError> {
+        let last_block = self.blocks.last().ok_or(LedgerError::NoGenesis)?;
+        let index = last_block.index + 1;
+        let prev_hash = last_block.hash.clone();
+        let timestamp = now().max(last_block.timestamp);
+        // refused before the mempool is drained
+        if !self
+            .producers(index)
+            .iter()
+            .any(|address| address == producer)
+        {
+            return Err(LedgerError::NotValidator {
+                index,
+                producer: producer.to_string(),
+            });
+        }
         let mut transactions = self.mempool.drain(10);
+        let mut dropped = vec![];
+        let mut locked = vec![];
+        let mut gas = 0u64;
+        let mut transition = Transition::new(&self.state, index, timestamp);
         transactions.retain(|transaction| {
-            if self.transaction_seen(&transaction.sender, transaction.nonce) {
-                return false;
-            }
-            if !transaction.verify() {
-                return false;
+            match transaction.validity_check(index, timestamp) {
+                Ok(()) => {}
+                Err(LedgerError::TransactionLocked { .. }) => {
+                    locked.push(transaction.clone());
+                    return false;
+                }
+                Err(_) => {
+                    dropped.push(Event::transaction_removed(transaction, false));
+                    return false;
+                }
             }
-            if !self.transaction_balance_verify(transaction) {
+            if gas.saturating_add(transaction.gas()) > MAX_BLOCK_GAS {
+                locked.push(transaction.clone());
                 return false;
             }
-            if !self.transaction_nonce_verify(transaction) {
-                return false;
+            let valid = !self.transaction_seen(&transaction.sender, transaction.nonce)
+                && transaction.verify_except_signature()
+                && (self.signatures.contains(transaction) || transaction.verify_signature())
+                && transition.apply(transaction).is_ok();
+            if valid {
+                gas += transaction.gas();
+            } else {
+                dropped.push(Event::transaction_removed(transaction, false));
             }
-
-            true
+            valid
         });
-        let last_block = self.blocks.last().unwrap();
-        let index = last_block.index + 1;
-        let prev_hash = last_block.hash.clone();
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        let block = Block::new(index, timestamp, prev_hash, transactions);
+        for event in &dropped {
+            if let Event::TransactionRemoved { hash, .. } = event {
+                self.signatures.remove(hash);
+            }
+        }
+        self.events.append(&mut dropped);
+        // back in the mempool until they are valid or fit in a block
+        for transaction in locked {
+            self.mempool.push(transaction);
+        }
+        let mut block = Block::new(
+            index,
+            timestamp,
+            prev_hash,
+            producer.to_string(),
+            transactions,
+        );
+        block.sign(signing_key)?;
         match self.block_add(block) {
             Ok(_) => {
-                assert_eq!(self.blocks.last().unwrap().index, index);
+                let block = self.blocks.last().unwrap();
+                assert_eq!(block.index, index);
+                let included = block
+                    .transactions
+                    .iter()
+                    .map(|transaction| Event::transaction_removed(transaction, true));
+                self.events.extend(included);
                 Ok(())
             }
             Err(e) => Err(e),
         }
     }
 
-    // pub fn last_seen_nonce(&self, sender: &str) -> Option<u64> {
-    //     self.nonce.get(sender).copied()
-    // }
+    pub fn block_get(&self, index: u64) -> Option<&Block> {
+        self.blocks.get(index as usize)
+    }
+
+    pub fn block_last(&self) -> Option<&Block> {
+        self.blocks.last()
+    }
+
+    pub fn balance_get(&self, address: &str) -> u64 {
+        *self
+            .state
+            .balance
+            .get(&(address.to_string(), None))
+            .unwrap_or(&0)
+    }
+
+    /// the balance of the account in the asset with the id
+    pub fn asset_balance_get(&self, address: &str, asset: &str) -> u64 {
+        *self
+            .state
+            .balance
+            .get(&(address.to_string(), Some(asset.to_string())))
+            .unwrap_or(&0)
+    }
+
+    pub fn asset_get(&self, id: &str) -> Option<&Asset> {
+        self.state.assets.get(id)
+    }
+
+    /// a contract by id, open or closed
+    pub fn htlc_get(&self, id: &str) -> Option<&Htlc> {
+        self.state.htlcs.get(id)
+    }
+
+    /// a smart contract by address
+    pub fn contract_get(&self, address: &str) -> Option<&Contract> {
+        self.state.contracts.get(address)
+    }
+
+    /// the value of the key in the storage of the contract
+    pub fn storage_get(&self, contract: &str, key: &[u8]) -> Option<&Vec<u8>> {
+        self.state
+            .storage
+            .get(&(contract.to_string(), key.to_vec()))
+    }
+
+    /// the result of the smart contract call with the transaction hash
+    pub fn receipt_get(&self, hash: &str) -> Option<&Receipt> {
+        self.state.receipts.get(hash)
+    }
 
-    // pub fn transaction_seen(&self, transaction: &Transaction) -> bool {
-    //     let last_known_nonce = *self.nonce.get(&transaction.sender).unwrap_or(&0);
-    //     transaction.nonce > last_known_nonce
-    // }
+    /// the bonded and unbonding stake of the address
+    pub fn stake_get(&self, address: &str) -> Stake {
+        self.state.stakes.get(address).cloned().unwrap_or_default()
+    }
 
-    // fn get_block(&self, index: u64) -> Option<&Block> {
-    //     self.blocks.get(index as usize)
-    // }
+    /// an unbonding by the hash of its unbond transaction
+    pub fn unbonding_get(&self, id: &str) -> Option<&Unbonding> {
+        self.state.unbondings.get(id)
+    }
 
-    // pub fn get_last_block(&self) -> Option<&Block> {
-    //     self.blocks.last()
-    // }
+    /// the validators of the epoch, none for an epoch not reached yet
+    /// no validators means only the genesis validator may produce
+    pub fn validators_get(&self, epoch: u64) -> Option<&Vec<Validator>> {
+        self.state.validators.get(&epoch)
+    }
 
-    // pub fn block_get_top_index(&self) -> u64 {
-    //     self.depth - 1
-    // }
+    /// the address allowed to send governance transactions, from the genesis parameters
+    pub fn governance_get(&self) -> Option<&str> {
+        self.state.governance.as_deref()
+    }
 
-    // fn get_blocks(&self) -> &Vec<Block> {
-    //     &self.blocks
-    // }
+    /// whether the account is frozen and its daily spending limit
+    pub fn control_get(&self, address: &str) -> Control {
+        self.state
+            .controls
+            .get(address)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// the native coins the account spent on the day, counted only while it has a limit
+    pub fn spending_get(&self, address: &str, day: u64) -> u64 {
+        let key = (address.to_string(), day);
+        *self.state.spending.get(&key).unwrap_or(&0)
+    }
+
+    /// the nonce of the last applied transaction of the sender, 0 if none
+    pub fn nonce_get(&self, sender: &str) -> u64 {
+        *self.state.nonce.get(sender).unwrap_or(&0)
+    }
+
+    /// the number of transactions waiting in the mempool
+    pub fn mempool_size(&self) -> usize {
+        self.mempool.len()
+    }
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::AssetOperation;
+    use crate::governance::GovernanceOperation;
+    use crate::htlc::{HtlcOperation, HtlcStatus};
+    use crate::multisig::Multisig;
+    use crate::staking::StakingOperation;
+    use crate::transaction::{Lock, Output};
+    use proptest::prelude::*;
+    use std::sync::OnceLock;
+    use utils::{SigningMode, Utils};
+
+    const KEY_PRIV: &str = "MIGEAgEAMBAGByqGSM49AgEGBSuBBAAKBG0wawIBAQQgGYFjSRDEGRmqvaJreuMY22pZz3TojuOm2dEmxhtbPTyhRANCAARSnspJBeKF9TrV5WmDTsJXb/wtihZ4YyXRmGASMIbzdYuW+B5vh1B/dRvZ15Ne8ehUQ/xH023fVx1STJzkSeoS";
+    const KEY_PUB: &str = "MFYwEAYHKoZIzj0CAQYFK4EEAAoDQgAEUp7KSQXihfU61eVpg07CV2/8LYoWeGMl0ZhgEjCG83WLlvgeb4dQf3Ub2deTXvHoVEP8R9Nt31cdUkyc5EnqEg==";
+    const ADDRESS: &str = "crt1rda9nkk7dv63muvhry040whcfaed5ccygdyzfr";
+    const RECEIVER: &str = "crt1az98ashxv3pa5x68yrz6ve8vz0ylaey28ccmy7";
+    const RECEIVER_PRIV: &str = "MIGEAgEAMBAGByqGSM49AgEGBSuBBAAKBG0wawIBAQQgYp6GnxdjxLvnucsaaTZ+J+FqtCdjbEaQsEqxk3KHJ3yhRANCAAR6X+Ws+hYmkOMIZTq/HMVBRbMcT1lADpd4z5c3MG6LzyuMDBMGOZ4C3gceN6I0/kzgQ/DWEZcNY4s6/WgLxUD1";
+    const RECEIVER_PUB: &str = "MFYwEAYHKoZIzj0CAQYFK4EEAAoDQgAEel/lrPoWJpDjCGU6vxzFQUWzHE9ZQA6XeM+XNzBui88rjAwTBjmeAt4HHjeiNP5M4EPw1hGXDWOLOv1oC8VA9Q==";
+
+    fn genesis_block() -> Block {
+        Genesis::new(ADDRESS).block()
+    }
+
+    /// the next block on the chain, `delay` seconds after the last one
+    fn block(
+        chain: &Chain,
+        delay: u64,
+        producer: &str,
+        signing_key: &SigningKey,
+        transactions: Vec<Transaction>,
+    ) -> Block {
+        let last = chain.block_last().unwrap();
+        let mut block = Block::new(
+            last.index + 1,
+            last.timestamp + delay,
+            last.hash.clone(),
+            producer.to_string(),
+            transactions,
+        );
+        block.sign(signing_key).unwrap();
+        block
+    }
+
+    fn signed_block(index: u64, timestamp: u64, prev_hash: &str) -> Block {
+        let signing_key = Utils::get_signing_key(KEY_PRIV).unwrap();
+        let mut block = Block::new(
+            index,
+            timestamp,
+            prev_hash.to_string(),
+            ADDRESS.to_string(),
+            vec![],
+        );
+        block
+            .sign_with(&signing_key, SigningMode::Deterministic)
+            .unwrap();
+        block
+    }
+
+    #[test]
+    fn test_block_add_errors() {
+        let mut chain = Chain::new(Genesis::new(ADDRESS));
+        assert!(matches!(
+            chain.block_add(signed_block(1, 0, "0")),
+            Err(LedgerError::NoGenesis)
+        ));
+
+        chain.block_add(genesis_block()).unwrap();
+        let genesis_hash = genesis_block().hash;
+        assert!(matches!(
+            chain.block_add(genesis_block()),
+            Err(LedgerError::BlockKnown { index: 0 })
+        ));
+        assert!(matches!(
+            chain.block_add(signed_block(1, 0, "invalid")),
+            Err(LedgerError::InvalidPrevHash { .. })
+        ));
+        assert!(matches!(
+            chain.block_add(Block::new(
+                1,
+                0,
+                genesis_hash.clone(),
+                ADDRESS.into(),
+                vec![]
+            )),
+            Err(LedgerError::MissingBlockSignature { index: 1 })
+        ));
+        let mut block = signed_block(1, 0, &genesis_hash);
+        block.hash = genesis_hash.clone();
+        assert!(matches!(
+            chain.block_add(block),
+            Err(LedgerError::InvalidBlockHash { index: 1 })
+        ));
+        assert!(matches!(
+            chain.block_add(signed_block(
+                1,
+                now() + 2 * MAX_TIMESTAMP_DRIFT,
+                &genesis_hash
+            )),
+            Err(LedgerError::TimestampInFuture { index: 1, .. })
+        ));
+
+        chain.block_add(signed_block(1, 10, &genesis_hash)).unwrap();
+        let hash = chain.blocks[1].hash.clone();
+        assert!(matches!(
+            chain.block_add(signed_block(2, 9, &hash)),
+            Err(LedgerError::TimestampNotMonotonic { index: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_orphans() {
+        let mut chain = Chain::new(Genesis::new(ADDRESS));
+        chain.block_add(genesis_block()).unwrap();
+        let genesis = genesis_block().hash;
+        let first = signed_block(1, 1, &genesis);
+        let second = signed_block(2, 2, &first.hash);
+        let third = signed_block(3, 3, &second.hash);
+
+        chain.block_add(third.clone()).unwrap();
+        chain.block_add(second.clone()).unwrap();
+        // one block kept per index, none too far ahead
+        chain.block_add(signed_block(2, 3, &first.hash)).unwrap();
+        let far = 1 + MAX_ORPHAN_BLOCKS;
+        chain.block_add(signed_block(far, 4, "0")).unwrap();
+        assert_eq!(chain.block_orphan.len(), 2);
+        assert_eq!(chain.block_orphan[&2].hash, second.hash);
+
+        chain.block_add(first).unwrap();
+        assert_eq!(chain.block_last().unwrap().hash, third.hash);
+        assert!(chain.block_orphan.is_empty());
+    }
+
+    #[test]
+    fn test_block_mint() {
+        let signing_key = Utils::get_signing_key(KEY_PRIV).unwrap();
+        let mut chain = Chain::new(Genesis::new(ADDRESS));
+        chain.block_add(genesis_block()).unwrap();
+        chain.state.balance.insert((ADDRESS.to_string(), None), 100);
+
+        let mut transaction = Transaction::new(1, 40, KEY_PUB, RECEIVER, None).unwrap();
+        transaction.sign(&signing_key).unwrap();
+        chain.transaction_add(transaction.clone()).unwrap();
+        assert!(matches!(
+            chain.transaction_add(transaction),
+            Err(LedgerError::TransactionKnown { .. })
+        ));
+        assert_eq!(chain.mempool_size(), 1);
+        chain.block_mint(ADDRESS, &signing_key).unwrap();
+
+        assert_eq!(chain.blocks.len(), 2);
+        assert!(chain.verify());
+        assert_eq!(chain.balance_get(ADDRESS), 60);
+        assert_eq!(chain.balance_get(RECEIVER), 40);
+        let events = chain.events_drain();
+        assert_eq!(events.len(), 5);
+        assert!(events.contains(&Event::BalanceChanged {
+            address: RECEIVER.to_string(),
+            asset: None,
+            balance: 40
+        }));
+        assert!(matches!(
+            events.last(),
+            Some(Event::TransactionRemoved { included: true, .. })
+        ));
+
+        let mut replay = Transaction::new(1, 40, KEY_PUB, RECEIVER, None).unwrap();
+        replay.sign(&signing_key).unwrap();
+        assert!(matches!(
+            chain.transaction_add(replay),
+            Err(LedgerError::TransactionSeen { nonce: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_index_reorg() {
+        let signing_key = Utils::get_signing_key(KEY_PRIV).unwrap();
+        let mut chain = Chain::new(Genesis::new(ADDRESS));
+        chain.block_add(genesis_block()).unwrap();
+        chain.state.balance.insert((ADDRESS.to_string(), None), 100);
+
+        let mut transaction = Transaction::new(1, 40, KEY_PUB, RECEIVER, None).unwrap();
+        transaction.sign(&signing_key).unwrap();
+        let hash = transaction.hash.clone();
+        chain.transaction_add(transaction).unwrap();
+        chain.block_mint(ADDRESS, &signing_key).unwrap();
+
+        let (location, transaction) = chain.transaction_get(&hash).unwrap();
+        assert_eq!(
+            location,
+            TransactionLocation {
+                block: 1,
+                position: 0
+            }
+        );
+        assert_eq!(transaction.hash, hash);
+        assert_eq!(chain.account_history(ADDRESS).len(), 1);
+        assert_eq!(chain.account_history(RECEIVER).len(), 1);
+
+        let genesis_hash = chain.blocks[0].hash.clone();
+        assert!(matches!(
+            chain.reorg(vec![signed_block(1, 1, &genesis_hash)]),
+            Err(LedgerError::BranchNotLonger { .. })
+        ));
+        let first = signed_block(1, 1, &genesis_hash);
+        let second = signed_block(2, 2, &first.hash);
+        let reverted = chain.reorg(vec![first, second]).unwrap();
+
+        assert_eq!(reverted.len(), 1);
+        assert_eq!(chain.blocks.len(), 3);
+        assert!(chain.transaction_get(&hash).is_none());
+        assert_eq!(chain.mempool_size(), 1);
+        assert!(chain.account_history(ADDRESS).is_empty());
+        assert!(chain.account_history(RECEIVER).is_empty());
+        assert_eq!(chain.balance_get(ADDRESS), 100);
+        assert_eq!(chain.balance_get(RECEIVER), 0);
+    }
+
+    #[test]
+    fn test_fork() {
+        let signing_key = Utils::get_signing_key(KEY_PRIV).unwrap();
+        let mut chain = Chain::new(Genesis::new(ADDRESS));
+        let genesis = genesis_block();
+        chain.block_add(genesis.clone()).unwrap();
+        chain.state.balance.insert((ADDRESS.to_string(), None), 100);
+
+        let mut transaction = Transaction::new(1, 40, KEY_PUB, RECEIVER, None).unwrap();
+        transaction.sign(&signing_key).unwrap();
+        let hash = transaction.hash.clone();
+        chain.transaction_add(transaction).unwrap();
+        chain.block_mint(ADDRESS, &signing_key).unwrap();
+        let top = chain.block_last().unwrap().hash.clone();
+        chain.events_drain();
+
+        // a branch as long as the chain is kept, a longer one is switched to
+        let first = signed_block(1, 1, &genesis.hash);
+        let second = signed_block(2, 2, &first.hash);
+        chain.block_add(first.clone()).unwrap();
+        assert_eq!(chain.block_last().unwrap().hash, top);
+        assert!(matches!(
+            chain.block_add(first),
+            Err(LedgerError::BlockKnown { index: 1 })
+        ));
+        chain.block_add(second.clone()).unwrap();
+        assert_eq!(chain.block_last().unwrap().hash, second.hash);
+        let events = chain.events_drain();
+        assert!(events.contains(&Event::BlockReverted {
+            index: 1,
+            hash: top.clone()
+        }));
+
+        // the reverted transaction waits for another block
+        assert!(chain.transaction_get(&hash).is_none());
+        assert!(chain.account_history(RECEIVER).is_empty());
+        assert_eq!(chain.balance_get(ADDRESS), 100);
+        assert_eq!(chain.mempool_size(), 1);
+        chain.block_mint(ADDRESS, &signing_key).unwrap();
+        assert_eq!(chain.transaction_get(&hash).unwrap().0.block, 3);
+        assert_eq!(chain.balance_get(RECEIVER), 40);
+
+        // a block on an unknown parent is no branch
+        assert!(matches!(
+            chain.block_add(signed_block(2, 3, "unknown")),
+            Err(LedgerError::BlockKnown { index: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_multisig() {
+        let (second_priv, second_pub) = Utils::generate_keypair().unwrap();
+        let multisig = Multisig::new(2, &[KEY_PUB, &second_pub]).unwrap();
+        let signing_key = Utils::get_signing_key(KEY_PRIV).unwrap();
+        let mut chain = Chain::new(Genesis::new(ADDRESS));
+        chain.block_add(genesis_block()).unwrap();
+        chain.state.balance.insert((multisig.address(), None), 100);
+
+        let mut transaction = Transaction::new_multisig(1, 40, multisig.clone(), RECEIVER).unwrap();
+        transaction.sign(&signing_key).unwrap();
+        assert!(matches!(
+            chain.transaction_add(transaction.clone()),
+            Err(LedgerError::InvalidTransaction { .. })
+        ));
+        // below the threshold in a block
+        let mut block = Block::new(
+            1,
+            0,
+            genesis_block().hash,
+            ADDRESS.into(),
+            vec![transaction.clone()],
+        );
+        block.sign(&signing_key).unwrap();
+        assert!(matches!(
+            chain.block_add(block),
+            Err(LedgerError::InvalidTransaction { .. })
+        ));
+
+        transaction
+            .sign(&Utils::get_signing_key(&second_priv).unwrap())
+            .unwrap();
+        chain.transaction_add(transaction).unwrap();
+        chain.block_mint(ADDRESS, &signing_key).unwrap();
+        assert_eq!(chain.balance_get(&multisig.address()), 60);
+        assert_eq!(chain.balance_get(RECEIVER), 40);
+    }
+
+    #[test]
+    fn test_validity() {
+        let signing_key = Utils::get_signing_key(KEY_PRIV).unwrap();
+        let signed = |nonce, valid_after, valid_until| {
+            let mut transaction = Transaction::new(nonce, 10, KEY_PUB, RECEIVER, None).unwrap();
+            transaction.validity_set(valid_after, valid_until).unwrap();
+            transaction.sign(&signing_key).unwrap();
+            transaction
+        };
+        let mut chain = Chain::new(Genesis::new(ADDRESS));
+        chain.block_add(genesis_block()).unwrap();
+        chain.state.balance.insert((ADDRESS.to_string(), None), 100);
+
+        assert!(matches!(
+            chain.transaction_add(signed(1, None, Some(Lock::Height(0)))),
+            Err(LedgerError::TransactionExpired { .. })
+        ));
+        let mut locked = signed(1, Some(Lock::Height(2)), None);
+        // the window is signed
+        assert!(matches!(
+            locked.validity_set(None, None),
+            Err(LedgerError::AlreadySigned)
+        ));
+        chain.transaction_add(locked).unwrap();
+        chain.block_mint(ADDRESS, &signing_key).unwrap();
+        assert!(chain.block_get(1).unwrap().transactions.is_empty());
+        assert_eq!(chain.mempool_size(), 1);
+        chain.block_mint(ADDRESS, &signing_key).unwrap();
+        assert_eq!(chain.block_get(2).unwrap().transactions.len(), 1);
+        assert_eq!(chain.balance_get(ADDRESS), 90);
+
+        // expired in the mempool once a block passes its window
+        let expiring = signed(2, None, Some(Lock::Height(3)));
+        chain.transaction_add(expiring.clone()).unwrap();
+        let last = chain.block_last().unwrap();
+        let block = signed_block(3, last.timestamp, &last.hash.clone());
+        chain.block_add(block).unwrap();
+        assert_eq!(chain.mempool_size(), 0);
+
+        let last = chain.block_last().unwrap();
+        let mut block = Block::new(
+            4,
+            last.timestamp,
+            last.hash.clone(),
+            ADDRESS.into(),
+            vec![expiring],
+        );
+        block.sign(&signing_key).unwrap();
+        assert!(matches!(
+            chain.block_add(block),
+            Err(LedgerError::TransactionExpired { .. })
+        ));
+    }
+
+    #[test]
+    fn test_batch() {
+        let signing_key = Utils::get_signing_key(KEY_PRIV).unwrap();
+        let (_, third) = Utils::generate_keypair().unwrap();
+        let third = Utils::address(&Utils::get_verifying_key(&third).unwrap());
+        let batch = |nonce, amount| {
+            let outputs = vec![
+                Output {
+                    receiver: RECEIVER.into(),
+                    amount,
+                },
+                Output {
+                    receiver: third.clone(),
+                    amount,
+                },
+            ];
+            let mut transaction = Transaction::new_batch(nonce, KEY_PUB, outputs).unwrap();
+            transaction.sign(&signing_key).unwrap();
+            transaction
+        };
+        let mut chain = Chain::new(Genesis::new(ADDRESS));
+        chain.block_add(genesis_block()).unwrap();
+        chain.state.balance.insert((ADDRESS.to_string(), None), 100);
+
+        // each output is affordable, the total is not
+        let mut block = Block::new(
+            1,
+            0,
+            genesis_block().hash,
+            ADDRESS.into(),
+            vec![batch(1, 60)],
+        );
+        block.sign(&signing_key).unwrap();
+        assert!(matches!(
+            chain.block_add(block),
+            Err(LedgerError::InsufficientBalance { amount: 120, .. })
+        ));
+
+        chain.transaction_add(batch(1, 30)).unwrap();
+        chain.block_mint(ADDRESS, &signing_key).unwrap();
+        assert_eq!(chain.block_get(1).unwrap().transactions.len(), 1);
+        assert_eq!(chain.balance_get(ADDRESS), 40);
+        assert_eq!(chain.balance_get(RECEIVER), 30);
+        assert_eq!(chain.balance_get(&third), 30);
+        assert_eq!(chain.account_history(&third).len(), 1);
+
+        chain.block_revert().unwrap();
+        assert_eq!(chain.balance_get(ADDRESS), 100);
+        assert_eq!(chain.balance_get(&third), 0);
+    }
+
+    #[test]
+    fn test_sequential() {
+        let signing_key = Utils::get_signing_key(KEY_PRIV).unwrap();
+        let signed = |nonce, amount| {
+            let mut transaction = Transaction::new(nonce, amount, KEY_PUB, RECEIVER, None).unwrap();
+            transaction.sign(&signing_key).unwrap();
+            transaction
+        };
+        let mut chain = Chain::new(Genesis::new(ADDRESS));
+        chain.block_add(genesis_block()).unwrap();
+        chain.state.balance.insert((ADDRESS.to_string(), None), 100);
+        chain
+            .state
+            .balance
+            .insert((RECEIVER.to_string(), None), u64::MAX - 100);
+
+        // each one is affordable on its own, not both
+        assert!(matches!(
+            chain.block_add(block(
+                &chain,
+                0,
+                ADDRESS,
+                &signing_key,
+                vec![signed(1, 60), signed(2, 60)]
+            )),
+            Err(LedgerError::InsufficientBalance { balance: 40, .. })
+        ));
+        chain.state.balance.insert((ADDRESS.to_string(), None), 200);
+        assert!(matches!(
+            chain.block_add(block(
+                &chain,
+                0,
+                ADDRESS,
+                &signing_key,
+                vec![signed(1, 60), signed(2, 60)]
+            )),
+            Err(LedgerError::BalanceOverflow { .. })
+        ));
+        assert_eq!(chain.balance_get(ADDRESS), 200);
+        assert_eq!(chain.nonce_get(ADDRESS), 0);
+
+        chain.state.balance.insert((RECEIVER.to_string(), None), 0);
+        for nonce in 1..=3 {
+            chain.transaction_add(signed(nonce, 60)).unwrap();
+        }
+        chain.block_mint(ADDRESS, &signing_key).unwrap();
+        assert_eq!(chain.block_get(1).unwrap().transactions.len(), 3);
+        assert_eq!(chain.balance_get(ADDRESS), 20);
+        assert_eq!(chain.balance_get(RECEIVER), 180);
+        assert_eq!(chain.nonce_get(ADDRESS), 3);
+    }
+
+    #[test]
+    fn test_assets() {
+        let signing_key = Utils::get_signing_key(KEY_PRIV).unwrap();
+        let signed = |mut transaction: Transaction| {
+            transaction.sign(&signing_key).unwrap();
+            transaction
+        };
+        let mut chain = Chain::new(Genesis::new(ADDRESS));
+        chain.block_add(genesis_block()).unwrap();
+        chain.state.balance.insert((ADDRESS.to_string(), None), 100);
+
+        let issue = Transaction::new_asset(
+            1,
+            KEY_PUB,
+            AssetOperation::Issue {
+                symbol: "USD".into(),
+                decimals: 2,
+            },
+        )
+        .unwrap();
+        let id = issue.hash().to_string();
+        let mint = Transaction::new_asset(
+            2,
+            KEY_PUB,
+            AssetOperation::Mint {
+                asset: id.clone(),
+                amount: 1_000,
+            },
+        )
+        .unwrap();
+        let mut transfer = Transaction::new(3, 300, KEY_PUB, RECEIVER, None).unwrap();
+        transfer.asset_set(Some(id.clone())).unwrap();
+        let burn = Transaction::new_asset(
+            4,
+            KEY_PUB,
+            AssetOperation::Burn {
+                asset: id.clone(),
+                amount: 200,
+            },
+        )
+        .unwrap();
+        for transaction in [issue, mint, transfer, burn] {
+            chain.transaction_add(signed(transaction)).unwrap();
+        }
+        chain.block_mint(ADDRESS, &signing_key).unwrap();
+        assert_eq!(chain.block_get(1).unwrap().transactions.len(), 4);
+        let asset = chain.asset_get(&id).unwrap();
+        assert_eq!(asset.issuer, ADDRESS);
+        assert_eq!(asset.supply, 800);
+        assert_eq!(chain.asset_balance_get(ADDRESS, &id), 500);
+        assert_eq!(chain.asset_balance_get(RECEIVER, &id), 300);
+        assert_eq!(chain.balance_get(ADDRESS), 100);
+        assert_eq!(chain.balance_get(RECEIVER), 0);
+
+        // only the issuer mints, only issued assets move
+        let (other_priv, other_pub) = Utils::generate_keypair().unwrap();
+        let mut mint = Transaction::new_asset(
+            1,
+            &other_pub,
+            AssetOperation::Mint {
+                asset: id.clone(),
+                amount: 1,
+            },
+        )
+        .unwrap();
+        mint.sign(&Utils::get_signing_key(&other_priv).unwrap())
+            .unwrap();
+        let mut unknown = Transaction::new(5, 1, KEY_PUB, RECEIVER, None).unwrap();
+        unknown.asset_set(Some("unknown".into())).unwrap();
+        assert!(matches!(
+            chain.block_add(block(&chain, 0, ADDRESS, &signing_key, vec![mint])),
+            Err(LedgerError::NotIssuer { .. })
+        ));
+        assert!(matches!(
+            chain.block_add(block(
+                &chain,
+                0,
+                ADDRESS,
+                &signing_key,
+                vec![signed(unknown)]
+            )),
+            Err(LedgerError::UnknownAsset(_))
+        ));
+
+        chain.block_revert().unwrap();
+        assert!(chain.asset_get(&id).is_none());
+        assert_eq!(chain.asset_balance_get(ADDRESS, &id), 0);
+        assert_eq!(chain.asset_balance_get(RECEIVER, &id), 0);
+    }
+
+    #[test]
+    fn test_htlc() {
+        let signing_key = Utils::get_signing_key(KEY_PRIV).unwrap();
+        let receiver_key = Utils::get_signing_key(RECEIVER_PRIV).unwrap();
+        let htlc = |nonce, sender: &str, signing_key: &SigningKey, operation| {
+            let mut transaction = Transaction::new_htlc(nonce, sender, operation).unwrap();
+            transaction.sign(signing_key).unwrap();
+            transaction
+        };
+        let open = |nonce, deadline| {
+            htlc(
+                nonce,
+                KEY_PUB,
+                &signing_key,
+                HtlcOperation::Open {
+                    receiver: RECEIVER.into(),
+                    amount: 40,
+                    hashlock: Utils::hash_data("secret"),
+                    deadline,
+                },
+            )
+        };
+        let mut chain = Chain::new(Genesis::new(ADDRESS));
+        chain.block_add(genesis_block()).unwrap();
+        chain.state.balance.insert((ADDRESS.to_string(), None), 100);
+
+        let claimed = open(1, Lock::Height(3));
+        let refunded = open(2, Lock::Height(3));
+        chain.transaction_add(claimed.clone()).unwrap();
+        chain.transaction_add(refunded.clone()).unwrap();
+        chain.block_mint(ADDRESS, &signing_key).unwrap();
+        assert_eq!(chain.balance_get(ADDRESS), 20);
+        assert_eq!(
+            chain.htlc_get(claimed.hash()).unwrap().status,
+            HtlcStatus::Open
+        );
+        // the receiver sees the contracts opened for it
+        let history = chain.account_history(RECEIVER);
+        let hashes: Vec<&str> = history.iter().map(|(_, t)| t.hash()).collect();
+        assert_eq!(hashes, [claimed.hash(), refunded.hash()]);
+
+        let claim = |nonce, preimage: &str| {
+            htlc(
+                nonce,
+                RECEIVER_PUB,
+                &receiver_key,
+                HtlcOperation::Claim {
+                    htlc: claimed.hash().to_string(),
+                    preimage: preimage.into(),
+                },
+            )
+        };
+        let refund = |nonce| {
+            htlc(
+                nonce,
+                KEY_PUB,
+                &signing_key,
+                HtlcOperation::Refund {
+                    htlc: refunded.hash().to_string(),
+                },
+            )
+        };
+        for transaction in [claim(1, "guess"), refund(3)] {
+            assert!(matches!(
+                chain.block_add(block(&chain, 0, ADDRESS, &signing_key, vec![transaction])),
+                Err(LedgerError::HtlcRejected { .. })
+            ));
+        }
+        chain
+            .block_add(block(
+                &chain,
+                0,
+                ADDRESS,
+                &signing_key,
+                vec![claim(1, "secret")],
+            ))
+            .unwrap();
+        assert_eq!(chain.balance_get(RECEIVER), 40);
+        assert_eq!(
+            chain.htlc_get(claimed.hash()).unwrap().status,
+            HtlcStatus::Claimed
+        );
+
+        // from the deadline on, refunds only
+        assert!(matches!(
+            chain.block_add(block(
+                &chain,
+                0,
+                ADDRESS,
+                &signing_key,
+                vec![claim(2, "secret")]
+            )),
+            Err(LedgerError::HtlcRejected { .. })
+        ));
+        chain
+            .block_add(block(&chain, 0, ADDRESS, &signing_key, vec![refund(3)]))
+            .unwrap();
+        assert_eq!(chain.balance_get(ADDRESS), 60);
+        assert_eq!(
+            chain.htlc_get(refunded.hash()).unwrap().status,
+            HtlcStatus::Refunded
+        );
+
+        chain.block_revert().unwrap();
+        chain.block_revert().unwrap();
+        assert_eq!(chain.balance_get(ADDRESS), 20);
+        assert_eq!(chain.balance_get(RECEIVER), 0);
+        assert_eq!(
+            chain.htlc_get(claimed.hash()).unwrap().status,
+            HtlcStatus::Open
+        );
+        chain.block_revert().unwrap();
+        assert_eq!(chain.balance_get(ADDRESS), 100);
+        assert!(chain.htlc_get(claimed.hash()).is_none());
+    }
+
+    #[cfg(feature = "contracts")]
+    #[test]
+    fn test_contracts() {
+        use crate::contract::{contract_address, ContractOperation, GAS_PRICE, MAX_GAS};
+
+        let signing_key = Utils::get_signing_key(KEY_PRIV).unwrap();
+        let contract = |nonce, operation| {
+            let mut transaction = Transaction::new_contract(nonce, KEY_PUB, operation).unwrap();
+            transaction.sign(&signing_key).unwrap();
+            transaction
+        };
+        let mut chain = Chain::new(Genesis::new(ADDRESS));
+        chain.block_add(genesis_block()).unwrap();
+        chain
+            .state
+            .balance
+            .insert((ADDRESS.to_string(), None), 1_000_000);
+
+        // the counter counts its calls, the refund pays the caller back
+        let counter = wat::parse_str(include_str!("../../contracts/wat/counter.wat")).unwrap();
+        let refund = wat::parse_str(
+            r#"(module
+                (import "env" "caller" (func $caller (param i32) (result i32)))
+                (import "env" "amount" (func $amount (result i64)))
+                (import "env" "transfer" (func $transfer (param i32 i32 i64) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "call") (local $len i32)
+                    (local.set $len (call $caller (i32.const 0)))
+                    (if (call $transfer (i32.const 0) (local.get $len) (call $amount))
+                        (then unreachable))))"#,
+        )
+        .unwrap();
+        let deploy_counter = contract(1, ContractOperation::Deploy { code: counter });
+        let deploy_refund = contract(2, ContractOperation::Deploy { code: refund });
+        let counter = contract_address(deploy_counter.hash());
+        let refund = contract_address(deploy_refund.hash());
+        let call = |nonce, contract: &str, amount, gas| {
+            let operation = ContractOperation::Call {
+                contract: contract.into(),
+                input: vec![],
+                amount,
+                gas,
+            };
+            Transaction::new_contract(nonce, KEY_PUB, operation).unwrap()
+        };
+        let signed = |mut transaction: Transaction| {
+            transaction.sign(&signing_key).unwrap();
+            transaction
+        };
+        chain
+            .block_add(block(
+                &chain,
+                0,
+                ADDRESS,
+                &signing_key,
+                vec![deploy_counter, deploy_refund],
+            ))
+            .unwrap();
+        assert_eq!(chain.contract_get(&counter).unwrap().deployer, ADDRESS);
+
+        let calls = vec![
+            signed(call(3, &counter, 10, 100_000)),
+            signed(call(4, &refund, 30, 100_000)),
+        ];
+        let hash = calls[0].hash().to_string();
+        let hash_refund = calls[1].hash().to_string();
+        chain
+            .block_add(block(&chain, 0, ADDRESS, &signing_key, calls))
+            .unwrap();
+        let count = 1u64.to_le_bytes().to_vec();
+        assert_eq!(chain.storage_get(&counter, b"count"), Some(&count));
+        assert_eq!(chain.receipt_get(&hash).unwrap().output, count);
+        // the sender pays for the gas used, the fee is burnt
+        let fee = (chain.receipt_get(&hash).unwrap().gas_used
+            + chain.receipt_get(&hash_refund).unwrap().gas_used)
+            * GAS_PRICE;
+        assert!(fee > 0);
+        assert_eq!(chain.balance_get(ADDRESS), 1_000_000 - 10 - fee);
+        assert_eq!(chain.balance_get(&counter), 10);
+        assert_eq!(chain.balance_get(&refund), 0);
+
+        // a failed call is not applied
+        assert!(matches!(
+            chain.block_add(block(
+                &chain,
+                0,
+                ADDRESS,
+                &signing_key,
+                vec![signed(call(5, &counter, 0, 10))]
+            )),
+            Err(LedgerError::ContractFailed { .. })
+        ));
+        assert!(matches!(
+            chain.block_add(block(
+                &chain,
+                0,
+                ADDRESS,
+                &signing_key,
+                vec![signed(call(5, &counter, 1_000_000, 100_000))]
+            )),
+            Err(LedgerError::InsufficientBalance { .. })
+        ));
+        assert_eq!(chain.storage_get(&counter, b"count"), Some(&count));
+
+        // the calls past the gas of a block wait in the mempool
+        let calls: Vec<_> = (5..11)
+            .map(|nonce| signed(call(nonce, &counter, 0, MAX_GAS)))
+            .collect();
+        assert!(matches!(
+            chain.block_add(block(&chain, 0, ADDRESS, &signing_key, calls.clone())),
+            Err(LedgerError::BlockGasExceeded { .. })
+        ));
+        for transaction in calls {
+            chain.transaction_add(transaction).unwrap();
+        }
+        chain.block_mint(ADDRESS, &signing_key).unwrap();
+        assert_eq!(chain.block_last().unwrap().transactions.len(), 5);
+        assert_eq!(chain.block_last().unwrap().gas(), MAX_BLOCK_GAS);
+        assert_eq!(chain.mempool_size(), 1);
+        chain.block_revert().unwrap();
+
+        chain.block_revert().unwrap();
+        assert_eq!(chain.storage_get(&counter, b"count"), None);
+        assert!(chain.receipt_get(&hash).is_none());
+        assert_eq!(chain.balance_get(ADDRESS), 1_000_000);
+        assert_eq!(chain.balance_get(&counter), 0);
+        chain.block_revert().unwrap();
+        assert!(chain.contract_get(&counter).is_none());
+    }
+
+    #[test]
+    fn test_staking() {
+        let signing_key = Utils::get_signing_key(KEY_PRIV).unwrap();
+        let receiver_key = Utils::get_signing_key(RECEIVER_PRIV).unwrap();
+        let staking = |nonce, sender: &str, signing_key: &SigningKey, operation| {
+            let mut transaction = Transaction::new_staking(nonce, sender, operation).unwrap();
+            transaction.sign(signing_key).unwrap();
+            transaction
+        };
+        let mut chain = Chain::new(Genesis::new(RECEIVER));
+        assert!(matches!(
+            chain.block_add(genesis_block()),
+            Err(LedgerError::InvalidGenesis)
+        ));
+        chain.block_add(Genesis::new(RECEIVER).block()).unwrap();
+        assert_eq!(chain.producer_due().as_deref(), Some(RECEIVER));
+        chain.state.balance.insert((ADDRESS.to_string(), None), 100);
+
+        let bond = staking(
+            1,
+            KEY_PUB,
+            &signing_key,
+            StakingOperation::Bond { amount: 60 },
+        );
+        let unbond = staking(
+            2,
+            KEY_PUB,
+            &signing_key,
+            StakingOperation::Unbond { amount: 20 },
+        );
+        let id = unbond.hash().to_string();
+        chain.transaction_add(bond).unwrap();
+        chain.transaction_add(unbond).unwrap();
+        assert!(matches!(
+            chain.block_mint(ADDRESS, &signing_key),
+            Err(LedgerError::NotValidator { index: 1, .. })
+        ));
+        chain.block_mint(RECEIVER, &receiver_key).unwrap();
+        assert_eq!(chain.balance_get(ADDRESS), 40);
+        assert_eq!(chain.stake_get(ADDRESS).bonded, 40);
+        let unbonding = chain.unbonding_get(&id).unwrap();
+        assert_eq!(unbonding.release, 1 + staking::UNBONDING_PERIOD);
+        let withdraw = StakingOperation::Withdraw {
+            unbonding: id.clone(),
+        };
+        let early = staking(3, KEY_PUB, &signing_key, withdraw);
+        assert!(matches!(
+            chain.block_add(block(&chain, 0, RECEIVER, &receiver_key, vec![early])),
+            Err(LedgerError::StakingRejected { .. })
+        ));
+
+        // the stake counts from the next epoch, until then the genesis validator produces
+        while chain.block_last().unwrap().index + 1 < staking::EPOCH_LENGTH {
+            chain.block_mint(RECEIVER, &receiver_key).unwrap();
+        }
+        assert!(chain.validators_get(1).is_none());
+        assert_eq!(chain.producer_due().as_deref(), Some(ADDRESS));
+        assert!(matches!(
+            chain.block_add(block(&chain, 0, RECEIVER, &receiver_key, vec![])),
+            Err(LedgerError::NotValidator { .. })
+        ));
+        chain.block_mint(ADDRESS, &signing_key).unwrap();
+        let validators = vec![Validator {
+            address: ADDRESS.into(),
+            stake: 40,
+        }];
+        assert_eq!(chain.validators_get(1), Some(&validators));
+
+        // a second block at the same height is evidence against the producer
+        let first = block(&chain, 0, ADDRESS, &signing_key, vec![]);
+        let second = block(&chain, 1, ADDRESS, &signing_key, vec![]);
+        chain.block_add(first).unwrap();
+        chain.events_drain();
+        // kept as a competing branch, the chain stays on the first block
+        chain.block_add(second.clone()).unwrap();
+        assert!(matches!(
+            chain.block_add(second),
+            Err(LedgerError::BlockKnown { .. })
+        ));
+        let evidence = match chain.events_drain().pop() {
+            Some(Event::Equivocation { evidence, .. }) => evidence,
+            event => panic!("expected an equivocation, got {:?}", event),
+        };
+        let slash = |nonce| {
+            let operation = StakingOperation::Slash {
+                evidence: evidence.clone(),
+            };
+            staking(nonce, RECEIVER_PUB, &receiver_key, operation)
+        };
+        chain
+            .block_add(block(&chain, 0, ADDRESS, &signing_key, vec![slash(1)]))
+            .unwrap();
+        assert_eq!(chain.stake_get(ADDRESS), Stake::default());
+        let status = chain.unbonding_get(&id).unwrap().status;
+        assert_eq!(status, staking::UnbondingStatus::Slashed);
+        assert_eq!(chain.balance_get(ADDRESS), 40);
+
+        // no validator has stake left, the genesis validator produces again
+        assert!(matches!(
+            chain.block_add(block(&chain, 0, RECEIVER, &receiver_key, vec![slash(2)])),
+            Err(LedgerError::StakingRejected { .. })
+        ));
+        chain
+            .block_add(block(&chain, 0, RECEIVER, &receiver_key, vec![]))
+            .unwrap();
+
+        chain.block_revert().unwrap();
+        chain.block_revert().unwrap();
+        assert_eq!(chain.stake_get(ADDRESS).bonded, 40);
+        let status = chain.unbonding_get(&id).unwrap().status;
+        assert_eq!(status, staking::UnbondingStatus::Unbonding);
+        chain.block_revert().unwrap();
+        chain.block_revert().unwrap();
+        assert!(chain.validators_get(1).is_none());
+    }
+
+    #[test]
+    fn test_slash_evidence() {
+        let signing_key = Utils::get_signing_key(KEY_PRIV).unwrap();
+        let receiver_key = Utils::get_signing_key(RECEIVER_PRIV).unwrap();
+        // two blocks by the address at the height, never added to the chain
+        let evidence = |index, prev_hash: &str| {
+            let header = |timestamp| {
+                let mut block =
+                    Block::new(index, timestamp, prev_hash.into(), ADDRESS.into(), vec![]);
+                block.sign(&signing_key).unwrap();
+                block.header().unwrap()
+            };
+            let (first, second) = (header(1), header(2));
+            Box::new(Equivocation { first, second })
+        };
+        let slash = |chain: &mut Chain, nonce, evidence| {
+            let operation = StakingOperation::Slash { evidence };
+            let mut transaction = Transaction::new_staking(nonce, RECEIVER_PUB, operation).unwrap();
+            transaction.sign(&receiver_key).unwrap();
+            let producer = chain.producer_due().unwrap();
+            let block = block(chain, 0, &producer, &signing_key, vec![transaction]);
+            chain.block_add(block)
+        };
+        let genesis = genesis_block();
+        let mut chain = Chain::new(Genesis::new(ADDRESS));
+        chain.block_add(genesis.clone()).unwrap();
+        chain.state.balance.insert((ADDRESS.to_string(), None), 100);
+        let bond = StakingOperation::Bond { amount: 60 };
+        let mut bond = Transaction::new_staking(1, KEY_PUB, bond).unwrap();
+        bond.sign(&signing_key).unwrap();
+        chain.transaction_add(bond).unwrap();
+        while chain.block_last().unwrap().index < staking::EPOCH_LENGTH {
+            chain.block_mint(ADDRESS, &signing_key).unwrap();
+        }
+        let last = chain.block_last().unwrap();
+        let recent = evidence(last.index, &last.prev_hash.clone());
+
+        // bonded during the first epoch, the address was not one of its validators
+        assert!(matches!(
+            slash(&mut chain, 1, evidence(1, &genesis.hash)),
+            Err(LedgerError::StakingRejected { reason, .. }) if reason.contains("not a validator")
+        ));
+        while chain.block_last().unwrap().index < staking::EPOCH_LENGTH + staking::UNBONDING_PERIOD
+        {
+            chain.block_mint(ADDRESS, &signing_key).unwrap();
+        }
+        assert!(matches!(
+            slash(&mut chain, 1, recent),
+            Err(LedgerError::StakingRejected { reason, .. }) if reason.contains("older than")
+        ));
+        assert_eq!(chain.stake_get(ADDRESS).bonded, 60);
+    }
+
+    #[test]
+    fn test_governance() {
+        let signing_key = Utils::get_signing_key(KEY_PRIV).unwrap();
+        let governance_key = Utils::get_signing_key(RECEIVER_PRIV).unwrap();
+        let control = |nonce, sender: &str, signing_key: &SigningKey, operation| {
+            let mut transaction = Transaction::new_governance(nonce, sender, operation).unwrap();
+            transaction.sign(signing_key).unwrap();
+            transaction
+        };
+        let transfer = |nonce, amount| {
+            let mut transaction = Transaction::new(nonce, amount, KEY_PUB, RECEIVER, None).unwrap();
+            transaction.sign(&signing_key).unwrap();
+            transaction
+        };
+        let parameters = Genesis {
+            validator: ADDRESS.to_string(),
+            governance: Some(RECEIVER.to_string()),
+        };
+        let genesis = parameters.block();
+        let mut chain = Chain::new(parameters);
+        assert!(matches!(
+            chain.block_add(genesis_block()),
+            Err(LedgerError::InvalidGenesis)
+        ));
+        chain.block_add(genesis).unwrap();
+        assert_eq!(chain.governance_get(), Some(RECEIVER));
+        chain.state.balance.insert((ADDRESS.to_string(), None), 100);
+        let yesterday = (now() / governance::DAY - 1) * governance::DAY;
+        let day = governance::day(yesterday);
+
+        let freeze = || GovernanceOperation::Freeze {
+            address: ADDRESS.into(),
+        };
+        assert!(matches!(
+            chain.block_add(block(
+                &chain,
+                yesterday,
+                ADDRESS,
+                &signing_key,
+                vec![control(1, KEY_PUB, &signing_key, freeze())]
+            )),
+            Err(LedgerError::NotGovernance { .. })
+        ));
+        let limit = GovernanceOperation::Limit {
+            address: ADDRESS.into(),
+            limit: Some(50),
+        };
+        let controls = vec![
+            control(1, RECEIVER_PUB, &governance_key, limit),
+            control(2, RECEIVER_PUB, &governance_key, freeze()),
+        ];
+        // the genesis block is at 0, the blocks are yesterday from here
+        let controls = block(&chain, yesterday, ADDRESS, &signing_key, controls);
+        chain.block_add(controls).unwrap();
+        let frozen = Control {
+            frozen: true,
+            limit: Some(50),
+        };
+        assert_eq!(chain.control_get(ADDRESS), frozen);
+        assert!(matches!(
+            chain.transaction_add(transfer(1, 10)),
+            Err(LedgerError::AccountFrozen { .. })
+        ));
+        assert!(matches!(
+            chain.block_add(block(
+                &chain,
+                0,
+                ADDRESS,
+                &signing_key,
+                vec![transfer(1, 10)]
+            )),
+            Err(LedgerError::AccountFrozen { .. })
+        ));
+
+        let unfreeze = GovernanceOperation::Unfreeze {
+            address: ADDRESS.into(),
+        };
+        let transactions = vec![
+            control(3, RECEIVER_PUB, &governance_key, unfreeze),
+            transfer(1, 30),
+        ];
+        chain
+            .block_add(block(&chain, 0, ADDRESS, &signing_key, transactions))
+            .unwrap();
+        assert_eq!(chain.spending_get(ADDRESS, day), 30);
+        assert!(matches!(
+            chain.block_add(block(
+                &chain,
+                0,
+                ADDRESS,
+                &signing_key,
+                vec![transfer(2, 30)]
+            )),
+            Err(LedgerError::DailyLimitExceeded { spent: 30, .. })
+        ));
+        assert!(matches!(
+            chain.transaction_add(transfer(2, 60)),
+            Err(LedgerError::DailyLimitExceeded { .. })
+        ));
+
+        // the limit resets with the day of the block
+        let today = block(
+            &chain,
+            governance::DAY,
+            ADDRESS,
+            &signing_key,
+            vec![transfer(2, 30)],
+        );
+        chain.block_add(today).unwrap();
+        assert_eq!(chain.spending_get(ADDRESS, day + 1), 30);
+        assert_eq!(chain.balance_get(ADDRESS), 40);
+
+        chain.block_revert().unwrap();
+        assert_eq!(chain.spending_get(ADDRESS, day + 1), 0);
+        chain.block_revert().unwrap();
+        assert_eq!(chain.spending_get(ADDRESS, day), 0);
+        assert_eq!(chain.control_get(ADDRESS), frozen);
+        chain.block_revert().unwrap();
+        assert_eq!(chain.control_get(ADDRESS), Control::default());
+    }
+
+    const ACCOUNTS: usize = 3;
+
+    fn accounts() -> &'static [(SigningKey, String, String)] {
+        static ACCOUNTS_KEYS: OnceLock<Vec<(SigningKey, String, String)>> = OnceLock::new();
+        ACCOUNTS_KEYS.get_or_init(|| {
+            (0..ACCOUNTS)
+                .map(|_| {
+                    let (private, public) = Utils::generate_keypair().unwrap();
+                    let signing_key = Utils::get_signing_key(&private).unwrap();
+                    let address = Utils::address(signing_key.verifying_key());
+                    (signing_key, public, address)
+                })
+                .collect()
+        })
+    }
+
+    /// small amounts, and amounts close to overflowing
+    fn amount() -> impl Strategy<Value = u64> {
+        prop_oneof![0..1_000u64, u64::MAX - 1_000..=u64::MAX, any::<u64>()]
+    }
+
+    /// a sender, its outputs and whether it uses a wrong nonce
+    type Transfer = (usize, Vec<(usize, u64)>, bool);
+
+    fn transfer() -> impl Strategy<Value = Transfer> {
+        (
+            0..ACCOUNTS,
+            prop::collection::vec((0..ACCOUNTS, amount()), 1..4),
+            prop::bool::weighted(0.1),
+        )
+    }
+
+    /// apply the blocks against a model of the balances in wide integers
+    fn supply_check(balances: Vec<u64>, blocks: Vec<Vec<Transfer>>) -> Result<(), TestCaseError> {
+        let accounts = accounts();
+        let producer = Utils::get_signing_key(KEY_PRIV).unwrap();
+        let mut chain = Chain::new(Genesis::new(ADDRESS));
+        chain.block_add(genesis_block()).unwrap();
+        for (i, balance) in balances.iter().enumerate() {
+            chain
+                .state
+                .balance
+                .insert((accounts[i].2.clone(), None), *balance);
+        }
+        let supply = |chain: &Chain| -> u128 {
+            accounts
+                .iter()
+                .map(|a| chain.balance_get(&a.2) as u128)
+                .sum()
+        };
+        let initial = supply(&chain);
+
+        for transfers in blocks {
+            // the model, in wide integers
+            let mut model: Vec<u128> = accounts
+                .iter()
+                .map(|a| chain.balance_get(&a.2) as u128)
+                .collect();
+            let mut nonces: Vec<u64> = accounts.iter().map(|a| chain.nonce_get(&a.2)).collect();
+            let mut valid = true;
+            let mut transactions = vec![];
+            for (sender, outputs, wrong_nonce) in transfers {
+                let (signing_key, public, _) = &accounts[sender];
+                let nonce = nonces[sender] + if wrong_nonce { 2 } else { 1 };
+                let mut transaction = match outputs.as_slice() {
+                    [(receiver, amount)] => {
+                        Transaction::new(nonce, *amount, public, &accounts[*receiver].2, None)
+                            .unwrap()
+                    }
+                    _ => {
+                        let outputs = outputs
+                            .iter()
+                            .map(|(receiver, amount)| Output {
+                                receiver: accounts[*receiver].2.clone(),
+                                amount: *amount,
+                            })
+                            .collect();
+                        match Transaction::new_batch(nonce, public, outputs) {
+                            Ok(transaction) => transaction,
+                            // the total overflows
+                            Err(_) => continue,
+                        }
+                    }
+                };
+                transaction
+                    .sign_with(signing_key, SigningMode::Deterministic)
+                    .unwrap();
+                transactions.push(transaction);
+
+                let total: u128 = outputs.iter().map(|(_, amount)| *amount as u128).sum();
+                valid &= !wrong_nonce && model[sender] >= total;
+                if valid {
+                    model[sender] -= total;
+                    for (receiver, amount) in &outputs {
+                        model[*receiver] += *amount as u128;
+                    }
+                    valid &= model.iter().all(|balance| *balance <= u64::MAX as u128);
+                    nonces[sender] = nonce;
+                }
+            }
+
+            let before: Vec<u64> = accounts.iter().map(|a| chain.balance_get(&a.2)).collect();
+            let result = chain.block_add(block(&chain, 0, ADDRESS, &producer, transactions));
+            prop_assert_eq!(result.is_ok(), valid, "{:?}", result.err());
+            let after: Vec<u64> = accounts.iter().map(|a| chain.balance_get(&a.2)).collect();
+            if valid {
+                prop_assert!(after.iter().zip(&model).all(|(a, m)| *a as u128 == *m));
+            } else {
+                prop_assert_eq!(after, before);
+            }
+            prop_assert_eq!(supply(&chain), initial);
+        }
+
+        while chain.blocks.len() > 1 {
+            chain.block_revert().unwrap();
+        }
+        for (i, balance) in balances.iter().enumerate() {
+            prop_assert_eq!(chain.balance_get(&accounts[i].2), *balance);
+            prop_assert_eq!(chain.nonce_get(&accounts[i].2), 0);
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        /// blocks apply like their transactions one after the other, all or nothing,
+        /// and never create or destroy funds
+        #[test]
+        fn test_supply(
+            balances in prop::collection::vec(amount(), ACCOUNTS),
+            blocks in prop::collection::vec(prop::collection::vec(transfer(), 0..6), 1..4),
+        ) {
+            supply_check(balances, blocks)?;
+        }
+    }
 }
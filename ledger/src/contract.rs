@@ -0,0 +1,340 @@
+use crate::error::LedgerError;
+use crate::executor::{operation_check, Executor};
+use crate::state::Transition;
+use crate::transaction::Transaction;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use utils::Utils;
+
+/// the maximum gas of a call
+pub const MAX_GAS: u64 = 10_000_000;
+/// the maximum sum of the gas of the calls in a block
+pub const MAX_BLOCK_GAS: u64 = 50_000_000;
+/// the native coins a call pays per gas used, the fee is burnt
+pub const GAS_PRICE: u64 = 1;
+/// the maximum number of bytes of a call input
+pub const MAX_INPUT_SIZE: usize = 1_024;
+/// whether this build runs contracts, a build without them can not validate the blocks
+/// that carry contract transactions and must not run a node
+pub const RUNTIME: bool = cfg!(feature = "contracts");
+
+/// a WebAssembly module deployed as an account, see the `contracts` crate for its interface
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Contract {
+    /// The address that deployed the contract
+    pub deployer: String,
+    #[serde(with = "hex::serde")]
+    pub code: Vec<u8>,
+}
+
+/// the result of a call, kept to show and to revert it
+#[derive(Clone, Debug, Serialize)]
+pub struct Receipt {
+    pub contract: String,
+    pub gas_used: u64,
+    #[serde(with = "hex::serde")]
+    pub output: Vec<u8>,
+    /// The values the call overwrote, by key
+    #[serde(skip)]
+    pub(crate) storage: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+    /// The payments of the contract, in order
+    #[serde(skip)]
+    pub(crate) transfers: Vec<(String, u64)>,
+}
+
+/// a smart contract operation, in place of a transfer
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContractOperation {
+    /// deploy a module at the address `contract_address` of the transaction hash
+    Deploy {
+        #[serde(with = "hex::serde")]
+        code: Vec<u8>,
+    },
+    /// pay the contract `amount` and run its `call` export with the input and at most `gas`,
+    /// the sender then pays `GAS_PRICE` for each gas used
+    Call {
+        contract: String,
+        #[serde(with = "hex::serde")]
+        input: Vec<u8>,
+        amount: u64,
+        gas: u64,
+    },
+}
+
+/// the address of the contract deployed by the transaction with the hash
+pub fn contract_address(hash: &str) -> String {
+    Utils::address_of(format!("contract:{}", hash).as_bytes())
+}
+
+impl Executor for ContractOperation {
+    fn verify(&self, transaction: &Transaction) -> Result<(), LedgerError> {
+        operation_check(transaction)?;
+        match self {
+            ContractOperation::Deploy { code } => runtime::validate(code),
+            ContractOperation::Call {
+                contract,
+                input,
+                gas,
+                ..
+            } => {
+                Utils::decode_address(contract).map_err(|e| LedgerError::InvalidAddress {
+                    address: contract.to_string(),
+                    reason: e.to_string(),
+                })?;
+                if input.len() > MAX_INPUT_SIZE {
+                    return Err(LedgerError::InvalidContract(format!(
+                        "the input has {} bytes, at most {}",
+                        input.len(),
+                        MAX_INPUT_SIZE
+                    )));
+                }
+                if *gas > MAX_GAS {
+                    return Err(LedgerError::InvalidContract(format!(
+                        "{} gas, at most {}",
+                        gas, MAX_GAS
+                    )));
+                }
+                runtime::enabled()
+            }
+        }
+    }
+
+    fn apply(&self, state: &mut Transition, transaction: &Transaction) -> Result<(), LedgerError> {
+        match self {
+            ContractOperation::Deploy { code } => {
+                let contract = Contract {
+                    deployer: transaction.sender.clone(),
+                    code: code.clone(),
+                };
+                state.contract_set(&contract_address(&transaction.hash), Some(contract));
+            }
+            ContractOperation::Call {
+                contract,
+                input,
+                amount,
+                gas,
+            } => {
+                let code = state
+                    .contract_get(contract)
+                    .ok_or_else(|| LedgerError::UnknownContract(contract.to_string()))?
+                    .code;
+                state.debit(&transaction.sender, &None, *amount)?;
+                state.credit(contract, &None, *amount)?;
+                let receipt = runtime::call(
+                    state,
+                    &transaction.sender,
+                    contract,
+                    &code,
+                    input,
+                    *amount,
+                    *gas,
+                )?;
+                state.debit(&transaction.sender, &None, receipt.gas_used * GAS_PRICE)?;
+                state.receipt_set(&transaction.hash, Some(receipt));
+            }
+        }
+        Ok(())
+    }
+
+    /// replay the receipt of a call backwards
+    fn revert(&self, state: &mut Transition, transaction: &Transaction) -> Result<(), LedgerError> {
+        match self {
+            ContractOperation::Deploy { .. } => {
+                state.contract_set(&contract_address(&transaction.hash), None);
+            }
+            ContractOperation::Call {
+                contract, amount, ..
+            } => {
+                let receipt = state
+                    .receipt_get(&transaction.hash)
+                    .ok_or_else(|| LedgerError::UnknownContract(contract.to_string()))?;
+                state.credit(&transaction.sender, &None, receipt.gas_used * GAS_PRICE)?;
+                for (receiver, paid) in receipt.transfers.iter().rev() {
+                    state.debit(receiver, &None, *paid)?;
+                    state.credit(contract, &None, *paid)?;
+                }
+                for (key, value) in receipt.storage {
+                    state.storage_set(contract, &key, value);
+                }
+                state.debit(contract, &None, *amount)?;
+                state.credit(&transaction.sender, &None, *amount)?;
+                state.receipt_set(&transaction.hash, None);
+            }
+        }
+        Ok(())
+    }
+
+    /// the deployed or the called contract
+    fn counterparties(&self, transaction: &Transaction) -> Vec<String> {
+        match self {
+            ContractOperation::Deploy { .. } => vec![contract_address(&transaction.hash)],
+            ContractOperation::Call { contract, .. } => vec![contract.clone()],
+        }
+    }
+}
+
+impl fmt::Display for ContractOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ContractOperation::Deploy { code } => write!(f, "deploy:{}", hex::encode(code)),
+            ContractOperation::Call {
+                contract,
+                input,
+                amount,
+                gas,
+            } => write!(
+                f,
+                "call:{}:{}:{}:{}",
+                contract,
+                hex::encode(input),
+                amount,
+                gas
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "contracts")]
+mod runtime {
+    use super::Receipt;
+    use crate::error::LedgerError;
+    use crate::state::Transition;
+    use contracts::{Context, Host};
+    use utils::Utils;
+
+    pub(super) fn enabled() -> Result<(), LedgerError> {
+        Ok(())
+    }
+
+    pub(super) fn validate(code: &[u8]) -> Result<(), LedgerError> {
+        contracts::validate(code).map_err(|e| LedgerError::InvalidContract(e.to_string()))
+    }
+
+    /// the storage and account of the contract, recording what a revert undoes
+    struct ContractHost<'s, 'a> {
+        state: &'s mut Transition<'a>,
+        contract: &'s str,
+        storage: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+        transfers: Vec<(String, u64)>,
+    }
+
+    impl Host for ContractHost<'_, '_> {
+        fn storage_get(&self, key: &[u8]) -> Option<Vec<u8>> {
+            self.state.storage_get(self.contract, key)
+        }
+
+        fn storage_set(&mut self, key: &[u8], value: Option<Vec<u8>>) {
+            if !self.storage.iter().any(|(written, _)| written == key) {
+                let previous = self.state.storage_get(self.contract, key);
+                self.storage.push((key.to_vec(), previous));
+            }
+            self.state.storage_set(self.contract, key, value);
+        }
+
+        /// all or nothing, a contract may carry on after a failed payment
+        fn transfer(&mut self, receiver: &str, amount: u64) -> Result<(), String> {
+            Utils::decode_address(receiver).map_err(|e| e.to_string())?;
+            let balance = self.state.balance_get(receiver, &None);
+            if receiver != self.contract && balance.checked_add(amount).is_none() {
+                return Err(format!("the balance of {} overflows", receiver));
+            }
+            self.state
+                .debit(self.contract, &None, amount)
+                .map_err(|e| e.to_string())?;
+            self.state
+                .credit(receiver, &None, amount)
+                .map_err(|e| e.to_string())?;
+            self.transfers.push((receiver.to_string(), amount));
+            Ok(())
+        }
+    }
+
+    /// run the contract for the caller, who paid it `amount`
+    pub(super) fn call(
+        state: &mut Transition,
+        caller: &str,
+        contract: &str,
+        code: &[u8],
+        input: &[u8],
+        amount: u64,
+        gas: u64,
+    ) -> Result<Receipt, LedgerError> {
+        let context = Context {
+            caller: caller.to_string(),
+            amount,
+            block_index: state.index(),
+            block_timestamp: state.timestamp(),
+        };
+        let mut host = ContractHost {
+            state,
+            contract,
+            storage: vec![],
+            transfers: vec![],
+        };
+        let outcome = contracts::call(code, &mut host, &context, input, gas).map_err(|e| {
+            LedgerError::ContractFailed {
+                contract: contract.to_string(),
+                reason: e.to_string(),
+            }
+        })?;
+        Ok(Receipt {
+            contract: contract.to_string(),
+            gas_used: outcome.gas_used,
+            output: outcome.output,
+            storage: host.storage,
+            transfers: host.transfers,
+        })
+    }
+}
+
+/// a node built without the `contracts` feature rejects contract operations
+#[cfg(not(feature = "contracts"))]
+mod runtime {
+    use super::Receipt;
+    use crate::error::LedgerError;
+    use crate::state::Transition;
+
+    pub(super) fn enabled() -> Result<(), LedgerError> {
+        Err(LedgerError::InvalidContract(
+            "contracts are not enabled".into(),
+        ))
+    }
+
+    pub(super) fn validate(_: &[u8]) -> Result<(), LedgerError> {
+        enabled()
+    }
+
+    pub(super) fn call(
+        _: &mut Transition,
+        _: &str,
+        _: &str,
+        _: &[u8],
+        _: &[u8],
+        _: u64,
+        _: u64,
+    ) -> Result<Receipt, LedgerError> {
+        Err(LedgerError::InvalidContract(
+            "contracts are not enabled".into(),
+        ))
+    }
+}
+
+// run with `cargo test -p ledger --no-default-features`
+#[cfg(all(test, not(feature = "contracts")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled() {
+        let sender = "MFYwEAYHKoZIzj0CAQYFK4EEAAoDQgAEUp7KSQXihfU61eVpg07CV2/8LYoWeGMl0ZhgEjCG83WLlvgeb4dQf3Ub2deTXvHoVEP8R9Nt31cdUkyc5EnqEg==";
+        const { assert!(!RUNTIME) };
+        let deploy = ContractOperation::Deploy {
+            code: b"\0asm\x01\0\0\0".to_vec(),
+        };
+        assert!(matches!(
+            Transaction::new_contract(1, sender, deploy),
+            Err(LedgerError::InvalidContract(_))
+        ));
+    }
+}
@@ -0,0 +1,136 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum LedgerError {
+    #[error("Transaction already seen\nsender:{sender} nonce:{nonce}")]
+    TransactionSeen { sender: String, nonce: u64 },
+    #[error("Transaction already known\nhash:{hash}")]
+    TransactionKnown { hash: String },
+    #[error("Invalid transaction\nsender:{sender} nonce:{nonce}")]
+    InvalidTransaction { sender: String, nonce: u64 },
+    #[error("Invalid nonce\nsender:{sender} expected:{expected} got:{got}")]
+    InvalidNonce {
+        sender: String,
+        expected: u64,
+        got: u64,
+    },
+    #[error("Insufficient balance\nsender:{sender} balance:{balance} amount:{amount}")]
+    InsufficientBalance {
+        sender: String,
+        balance: u64,
+        amount: u64,
+    },
+    #[error("Balance overflow\naddress:{address} balance:{balance} amount:{amount}")]
+    BalanceOverflow {
+        address: String,
+        balance: u64,
+        amount: u64,
+    },
+    #[error("Transaction not valid yet\nsender:{sender} nonce:{nonce} valid_after:{valid_after}")]
+    TransactionLocked {
+        sender: String,
+        nonce: u64,
+        valid_after: String,
+    },
+    #[error("Transaction expired\nsender:{sender} nonce:{nonce} valid_until:{valid_until}")]
+    TransactionExpired {
+        sender: String,
+        nonce: u64,
+        valid_until: String,
+    },
+    #[error("Memo too large\nsize:{size} limit:{limit}")]
+    MemoTooLarge { size: usize, limit: usize },
+    #[error("Transaction already signed")]
+    AlreadySigned,
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
+    #[error("Invalid asset: {0}")]
+    InvalidAsset(String),
+    #[error("Unknown asset {0}")]
+    UnknownAsset(String),
+    #[error("Not the issuer of the asset\nasset:{asset} sender:{sender}")]
+    NotIssuer { asset: String, sender: String },
+    #[error("Invalid HTLC: {0}")]
+    InvalidHtlc(String),
+    #[error("HTLC operation rejected\nhtlc:{htlc} reason:{reason}")]
+    HtlcRejected { htlc: String, reason: String },
+    #[error("Invalid smart contract: {0}")]
+    InvalidContract(String),
+    #[error("Unknown smart contract {0}")]
+    UnknownContract(String),
+    #[error("Smart contract call failed\ncontract:{contract} reason:{reason}")]
+    ContractFailed { contract: String, reason: String },
+    #[error("Invalid staking operation: {0}")]
+    InvalidStaking(String),
+    #[error("Staking operation rejected\naddress:{address} reason:{reason}")]
+    StakingRejected { address: String, reason: String },
+    #[error("Block producer is not a validator\nindex:{index} producer:{producer}")]
+    NotValidator { index: u64, producer: String },
+    #[error("Not the governance address\nsender:{sender}")]
+    NotGovernance { sender: String },
+    #[error("Account frozen\naddress:{address}")]
+    AccountFrozen { address: String },
+    #[error("Daily spending limit exceeded\naddress:{address} limit:{limit} spent:{spent} amount:{amount}")]
+    DailyLimitExceeded {
+        address: String,
+        limit: u64,
+        spent: u64,
+        amount: u64,
+    },
+    #[error("Invalid outputs: {0}")]
+    InvalidOutputs(String),
+    #[error("Invalid address\naddress:{address} reason:{reason}")]
+    InvalidAddress { address: String, reason: String },
+    #[error("Invalid multisig: {0}")]
+    InvalidMultisig(String),
+    #[error("Invalid prev_hash\nexpected:{expected} got:{got}")]
+    InvalidPrevHash { expected: String, got: String },
+    #[error("Chain has no genesis block")]
+    NoGenesis,
+    #[error("Invalid genesis block")]
+    InvalidGenesis,
+    #[error("Block already known\nindex:{index}")]
+    BlockKnown { index: u64 },
+    #[error("Invalid block hash\nindex:{index}")]
+    InvalidBlockHash { index: u64 },
+    #[error("Missing block signature\nindex:{index}")]
+    MissingBlockSignature { index: u64 },
+    #[error("Invalid block signature\nindex:{index}")]
+    InvalidBlockSignature { index: u64 },
+    #[error("Block timestamp before previous block\nindex:{index} timestamp:{timestamp} prev_timestamp:{prev_timestamp}")]
+    TimestampNotMonotonic {
+        index: u64,
+        timestamp: u64,
+        prev_timestamp: u64,
+    },
+    #[error(
+        "Block timestamp too far in the future\nindex:{index} timestamp:{timestamp} now:{now}"
+    )]
+    TimestampInFuture {
+        index: u64,
+        timestamp: u64,
+        now: u64,
+    },
+    #[error("Too many transactions in block\nindex:{index} count:{count} limit:{limit}")]
+    TooManyTransactions {
+        index: u64,
+        count: usize,
+        limit: usize,
+    },
+    #[error("Block too large\nindex:{index} size:{size} limit:{limit}")]
+    BlockTooLarge {
+        index: u64,
+        size: usize,
+        limit: usize,
+    },
+    #[error("Block gas exceeded\nindex:{index} gas:{gas} limit:{limit}")]
+    BlockGasExceeded { index: u64, gas: u64, limit: u64 },
+    #[error("Invalid branch")]
+    InvalidBranch,
+    #[error("Branch not longer than the chain\nlength:{length} current:{current}")]
+    BranchNotLonger { length: usize, current: usize },
+    #[error("Duplicate transaction in block\nindex:{index} hash:{hash}")]
+    DuplicateTransaction { index: u64, hash: String },
+}
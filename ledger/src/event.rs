@@ -0,0 +1,83 @@
+use crate::staking::Equivocation;
+use crate::transaction::Transaction;
+use serde::{Deserialize, Serialize};
+
+/// a change of the chain state, recorded by `Chain` as it happens
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Event {
+    BlockApplied {
+        index: u64,
+        hash: String,
+    },
+    /// the block was removed from the top of the chain by a reorg
+    BlockReverted {
+        index: u64,
+        hash: String,
+    },
+    /// the transaction entered the mempool
+    TransactionAdded {
+        hash: String,
+        sender: String,
+        receivers: Vec<String>,
+    },
+    /// the transaction left the mempool, either included in a block or dropped
+    TransactionRemoved {
+        hash: String,
+        sender: String,
+        receivers: Vec<String>,
+        included: bool,
+    },
+    /// the balance in the native coin, or in the asset with the id
+    BalanceChanged {
+        address: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        asset: Option<String>,
+        balance: u64,
+    },
+    /// the producer signed two blocks at the height, the evidence slashes its stake
+    Equivocation {
+        producer: String,
+        index: u64,
+        evidence: Box<Equivocation>,
+    },
+}
+
+impl Event {
+    pub(crate) fn transaction_removed(transaction: &Transaction, included: bool) -> Event {
+        Event::TransactionRemoved {
+            hash: transaction.hash.clone(),
+            sender: transaction.sender.clone(),
+            receivers: transaction.receivers(),
+            included,
+        }
+    }
+
+    /// the name of the variant, as serialized in the `type` tag
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::BlockApplied { .. } => "BlockApplied",
+            Event::BlockReverted { .. } => "BlockReverted",
+            Event::TransactionAdded { .. } => "TransactionAdded",
+            Event::TransactionRemoved { .. } => "TransactionRemoved",
+            Event::BalanceChanged { .. } => "BalanceChanged",
+            Event::Equivocation { .. } => "Equivocation",
+        }
+    }
+
+    /// whether the event is about the account
+    /// block events concern every account
+    pub fn concerns(&self, address: &str) -> bool {
+        match self {
+            Event::BlockApplied { .. } | Event::BlockReverted { .. } => true,
+            Event::TransactionAdded {
+                sender, receivers, ..
+            }
+            | Event::TransactionRemoved {
+                sender, receivers, ..
+            } => sender == address || receivers.iter().any(|r| r == address),
+            Event::BalanceChanged { address: a, .. } => a == address,
+            Event::Equivocation { producer, .. } => producer == address,
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use crate::asset::AssetOperation;
+use crate::contract::ContractOperation;
+use crate::error::LedgerError;
+use crate::governance::GovernanceOperation;
+use crate::htlc::HtlcOperation;
+use crate::staking::StakingOperation;
+use crate::state::Transition;
+use crate::transaction::Transaction;
+
+/// what a transaction does, see `Transaction::kind`
+/// a new operation is a variant with an `Executor`, the chain only sees the transition
+#[derive(Clone, Copy, Debug)]
+pub enum TransactionKind<'a> {
+    /// pay the receiver, or each output of a batch
+    Transfer,
+    Asset(&'a AssetOperation),
+    Htlc(&'a HtlcOperation),
+    Contract(&'a ContractOperation),
+    Staking(&'a StakingOperation),
+    Governance(&'a GovernanceOperation),
+}
+
+impl TransactionKind<'_> {
+    pub fn executor(&self) -> &dyn Executor {
+        match self {
+            TransactionKind::Transfer => &Transfer,
+            TransactionKind::Asset(operation) => *operation,
+            TransactionKind::Htlc(operation) => *operation,
+            TransactionKind::Contract(operation) => *operation,
+            TransactionKind::Staking(operation) => *operation,
+            TransactionKind::Governance(operation) => *operation,
+        }
+    }
+}
+
+/// validates and carries out one kind of transaction
+/// the nonce, hash and signatures are checked for every kind by the chain
+pub trait Executor {
+    /// the checks that need no state
+    fn verify(&self, transaction: &Transaction) -> Result<(), LedgerError>;
+
+    /// change the state, the changes are dropped if it fails
+    fn apply(&self, state: &mut Transition, transaction: &Transaction) -> Result<(), LedgerError>;
+
+    /// undo `apply` on the state it left
+    fn revert(&self, state: &mut Transition, transaction: &Transaction) -> Result<(), LedgerError>;
+
+    /// the transaction may move an asset instead of the native coin, see `Transaction::asset_set`
+    fn transfers_asset(&self) -> bool {
+        false
+    }
+
+    /// the addresses besides the sender the transaction names, see `Transaction::addresses`
+    fn counterparties(&self, _transaction: &Transaction) -> Vec<String> {
+        vec![]
+    }
+}
+
+/// the native coin or an asset from the sender to the outputs of the transaction
+pub struct Transfer;
+
+impl Executor for Transfer {
+    /// a batch has a bounded number of outputs with a total that fits, and no single receiver
+    fn verify(&self, transaction: &Transaction) -> Result<(), LedgerError> {
+        transaction.outputs_check()
+    }
+
+    fn apply(&self, state: &mut Transition, transaction: &Transaction) -> Result<(), LedgerError> {
+        if let Some(asset) = &transaction.asset {
+            state.asset_get(asset)?;
+        }
+        state.debit(&transaction.sender, &transaction.asset, total(transaction)?)?;
+        for (receiver, amount) in transaction.outputs() {
+            state.credit(receiver, &transaction.asset, amount)?;
+        }
+        Ok(())
+    }
+
+    fn revert(&self, state: &mut Transition, transaction: &Transaction) -> Result<(), LedgerError> {
+        for (receiver, amount) in transaction.outputs().into_iter().rev() {
+            state.debit(receiver, &transaction.asset, amount)?;
+        }
+        state.credit(&transaction.sender, &transaction.asset, total(transaction)?)
+    }
+
+    fn transfers_asset(&self) -> bool {
+        true
+    }
+
+    fn counterparties(&self, transaction: &Transaction) -> Vec<String> {
+        transaction.receivers()
+    }
+}
+
+/// an operation in place of a transfer pays nothing directly
+pub(crate) fn operation_check(transaction: &Transaction) -> Result<(), LedgerError> {
+    if !transaction.outputs.is_empty()
+        || !transaction.receiver.is_empty()
+        || transaction.amount != 0
+    {
+        return Err(LedgerError::InvalidOutputs(
+            "an operation has no receiver".into(),
+        ));
+    }
+    Ok(())
+}
+
+fn total(transaction: &Transaction) -> Result<u64, LedgerError> {
+    transaction
+        .total()
+        .ok_or_else(|| LedgerError::InvalidTransaction {
+            sender: transaction.sender.clone(),
+            nonce: transaction.nonce,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_PUB: &str = "MFYwEAYHKoZIzj0CAQYFK4EEAAoDQgAEUp7KSQXihfU61eVpg07CV2/8LYoWeGMl0ZhgEjCG83WLlvgeb4dQf3Ub2deTXvHoVEP8R9Nt31cdUkyc5EnqEg==";
+    const RECEIVER: &str = "crt1az98ashxv3pa5x68yrz6ve8vz0ylaey28ccmy7";
+
+    #[test]
+    fn test() {
+        let transfer = Transaction::new(1, 10, KEY_PUB, RECEIVER, None).unwrap();
+        assert!(matches!(transfer.kind(), Some(TransactionKind::Transfer)));
+        assert!(transfer.kind().unwrap().executor().transfers_asset());
+
+        let issue = AssetOperation::Issue {
+            symbol: "USD".into(),
+            decimals: 2,
+        };
+        let mut transaction = Transaction::new_asset(1, KEY_PUB, issue).unwrap();
+        assert!(matches!(
+            transaction.kind(),
+            Some(TransactionKind::Asset(_))
+        ));
+        assert!(transaction.asset_set(Some("usd".into())).is_err());
+
+        // one operation per transaction
+        transaction.htlc = Some(HtlcOperation::Refund { htlc: "id".into() });
+        assert!(transaction.kind().is_none());
+        assert!(!transaction.verify_except_signature());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::error::LedgerError;
+use crate::executor::{operation_check, Executor};
+use crate::state::Transition;
+use crate::transaction::Transaction;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use utils::Utils;
+
+/// the seconds of a day, spending limits reset with the block timestamp day
+pub const DAY: u64 = 86_400;
+
+/// the day of a block timestamp, in days since the unix epoch
+pub fn day(timestamp: u64) -> u64 {
+    timestamp / DAY
+}
+
+/// the controls the governance set on an account
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Control {
+    /// A frozen account sends no transactions, it still receives
+    pub frozen: bool,
+    /// The most native coins the account may spend a day, unlimited if none
+    pub limit: Option<u64>,
+}
+
+/// a control transaction, only by the governance address named by the genesis block
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GovernanceOperation {
+    Freeze {
+        address: String,
+    },
+    Unfreeze {
+        address: String,
+    },
+    /// set the daily spending limit of the address, remove it if none
+    Limit {
+        address: String,
+        limit: Option<u64>,
+    },
+}
+
+impl GovernanceOperation {
+    pub fn address(&self) -> &str {
+        match self {
+            GovernanceOperation::Freeze { address }
+            | GovernanceOperation::Unfreeze { address }
+            | GovernanceOperation::Limit { address, .. } => address,
+        }
+    }
+}
+
+impl Executor for GovernanceOperation {
+    fn verify(&self, transaction: &Transaction) -> Result<(), LedgerError> {
+        operation_check(transaction)?;
+        let address = self.address();
+        Utils::decode_address(address).map_err(|e| LedgerError::InvalidAddress {
+            address: address.to_string(),
+            reason: e.to_string(),
+        })?;
+        Ok(())
+    }
+
+    fn apply(&self, state: &mut Transition, transaction: &Transaction) -> Result<(), LedgerError> {
+        if state.governance() != Some(transaction.sender.as_str()) {
+            return Err(LedgerError::NotGovernance {
+                sender: transaction.sender.clone(),
+            });
+        }
+        let address = self.address();
+        let mut control = state.control_get(address);
+        // the control it replaces, to revert it
+        state.control_undo_set(&transaction.hash, Some(control.clone()));
+        match self {
+            GovernanceOperation::Freeze { .. } => control.frozen = true,
+            GovernanceOperation::Unfreeze { .. } => control.frozen = false,
+            GovernanceOperation::Limit { limit, .. } => control.limit = *limit,
+        }
+        state.control_set(address, control);
+        Ok(())
+    }
+
+    fn revert(&self, state: &mut Transition, transaction: &Transaction) -> Result<(), LedgerError> {
+        let control = state.control_undo_get(&transaction.hash).ok_or_else(|| {
+            LedgerError::NotGovernance {
+                sender: transaction.sender.clone(),
+            }
+        })?;
+        state.control_set(self.address(), control);
+        state.control_undo_set(&transaction.hash, None);
+        Ok(())
+    }
+
+    fn counterparties(&self, _transaction: &Transaction) -> Vec<String> {
+        vec![self.address().to_string()]
+    }
+}
+
+/// the spending of the day with `spent` so far can grow by `amount` within the limit
+pub(crate) fn limit_check(
+    address: &str,
+    limit: Option<u64>,
+    spent: u64,
+    amount: u64,
+) -> Result<u64, LedgerError> {
+    let total = spent.saturating_add(amount);
+    match limit {
+        Some(limit) if total > limit => Err(LedgerError::DailyLimitExceeded {
+            address: address.to_string(),
+            limit,
+            spent,
+            amount,
+        }),
+        _ => Ok(total),
+    }
+}
+
+impl fmt::Display for GovernanceOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GovernanceOperation::Freeze { address } => write!(f, "freeze:{}", address),
+            GovernanceOperation::Unfreeze { address } => write!(f, "unfreeze:{}", address),
+            GovernanceOperation::Limit { address, limit } => match limit {
+                Some(limit) => write!(f, "limit:{}:{}", address, limit),
+                None => write!(f, "limit:{}:none", address),
+            },
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use crate::error::LedgerError;
+use crate::executor::{operation_check, Executor};
+use crate::state::Transition;
+use crate::transaction::{Lock, Transaction};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use utils::Utils;
+
+/// the maximum number of bytes of a preimage
+pub const MAX_PREIMAGE_SIZE: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HtlcStatus {
+    Open,
+    Claimed,
+    Refunded,
+}
+
+/// funds locked to a receiver, claimable with the preimage of the hashlock before the deadline
+/// and refundable to the sender from the deadline on
+/// identified by the hash of the transaction that opened it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Htlc {
+    pub sender: String,
+    pub receiver: String,
+    pub amount: u64,
+    /// The id of the asset locked, the native coin if none
+    pub asset: Option<String>,
+    /// The hex SHA-256 of the preimage, see `Utils::hash_data`
+    pub hashlock: String,
+    pub deadline: Lock,
+    pub status: HtlcStatus,
+}
+
+/// an operation on a hashed time-locked contract, in place of a transfer
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HtlcOperation {
+    /// lock the amount of the sender, in the asset of the transaction
+    Open {
+        receiver: String,
+        amount: u64,
+        hashlock: String,
+        deadline: Lock,
+    },
+    /// pay the contract to its receiver, who reveals the preimage
+    Claim { htlc: String, preimage: String },
+    /// pay the contract back to its sender
+    Refund { htlc: String },
+}
+
+impl HtlcOperation {
+    /// the contract the operation closes, none for an open as the contract does not exist yet
+    pub fn htlc(&self) -> Option<&str> {
+        match self {
+            HtlcOperation::Open { .. } => None,
+            HtlcOperation::Claim { htlc, .. } | HtlcOperation::Refund { htlc } => Some(htlc),
+        }
+    }
+}
+
+impl Executor for HtlcOperation {
+    fn verify(&self, transaction: &Transaction) -> Result<(), LedgerError> {
+        operation_check(transaction)?;
+        match self {
+            HtlcOperation::Open {
+                receiver, hashlock, ..
+            } => {
+                Utils::decode_address(receiver).map_err(|e| LedgerError::InvalidAddress {
+                    address: receiver.to_string(),
+                    reason: e.to_string(),
+                })?;
+                if hashlock.len() != 64
+                    || !hashlock
+                        .bytes()
+                        .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+                {
+                    return Err(LedgerError::InvalidHtlc(format!(
+                        "the hashlock {} is not a lowercase hex SHA-256",
+                        hashlock
+                    )));
+                }
+            }
+            HtlcOperation::Claim { preimage, .. } => {
+                if preimage.len() > MAX_PREIMAGE_SIZE {
+                    return Err(LedgerError::InvalidHtlc(format!(
+                        "the preimage has {} bytes, at most {}",
+                        preimage.len(),
+                        MAX_PREIMAGE_SIZE
+                    )));
+                }
+            }
+            HtlcOperation::Refund { .. } => {}
+        }
+        Ok(())
+    }
+
+    /// a contract is claimed before its deadline and refunded from it on
+    fn apply(&self, state: &mut Transition, transaction: &Transaction) -> Result<(), LedgerError> {
+        let sender = &transaction.sender;
+        let (index, timestamp) = (state.index(), state.timestamp());
+        match self {
+            HtlcOperation::Open {
+                receiver,
+                amount,
+                hashlock,
+                deadline,
+            } => {
+                if deadline.reached(index, timestamp) {
+                    return Err(rejected(&transaction.hash, "the deadline has passed"));
+                }
+                if let Some(asset) = &transaction.asset {
+                    state.asset_get(asset)?;
+                }
+                state.debit(sender, &transaction.asset, *amount)?;
+                let htlc = Htlc {
+                    sender: sender.clone(),
+                    receiver: receiver.clone(),
+                    amount: *amount,
+                    asset: transaction.asset.clone(),
+                    hashlock: hashlock.clone(),
+                    deadline: *deadline,
+                    status: HtlcStatus::Open,
+                };
+                state.htlc_set(&transaction.hash, Some(htlc));
+            }
+            HtlcOperation::Claim { htlc: id, preimage } => {
+                let mut htlc = open(state, id)?;
+                if &htlc.receiver != sender {
+                    return Err(rejected(id, "only the receiver claims"));
+                }
+                if htlc.deadline.reached(index, timestamp) {
+                    return Err(rejected(id, "the deadline has passed"));
+                }
+                if Utils::hash_data(preimage) != htlc.hashlock {
+                    return Err(rejected(id, "the preimage does not match"));
+                }
+                state.credit(sender, &htlc.asset, htlc.amount)?;
+                htlc.status = HtlcStatus::Claimed;
+                state.htlc_set(id, Some(htlc));
+            }
+            HtlcOperation::Refund { htlc: id } => {
+                let mut htlc = open(state, id)?;
+                if &htlc.sender != sender {
+                    return Err(rejected(id, "only the sender is refunded"));
+                }
+                if !htlc.deadline.reached(index, timestamp) {
+                    return Err(rejected(id, "the deadline has not passed"));
+                }
+                state.credit(sender, &htlc.asset, htlc.amount)?;
+                htlc.status = HtlcStatus::Refunded;
+                state.htlc_set(id, Some(htlc));
+            }
+        }
+        Ok(())
+    }
+
+    fn revert(&self, state: &mut Transition, transaction: &Transaction) -> Result<(), LedgerError> {
+        let sender = &transaction.sender;
+        match self {
+            HtlcOperation::Open { amount, .. } => {
+                state.credit(sender, &transaction.asset, *amount)?;
+                state.htlc_set(&transaction.hash, None);
+            }
+            HtlcOperation::Claim { htlc: id, .. } | HtlcOperation::Refund { htlc: id } => {
+                let mut htlc = state
+                    .htlc_get(id)
+                    .ok_or_else(|| rejected(id, "unknown contract"))?;
+                state.debit(sender, &htlc.asset, htlc.amount)?;
+                htlc.status = HtlcStatus::Open;
+                state.htlc_set(id, Some(htlc));
+            }
+        }
+        Ok(())
+    }
+
+    /// an opened contract locks the asset of the transaction
+    fn transfers_asset(&self) -> bool {
+        matches!(self, HtlcOperation::Open { .. })
+    }
+
+    /// the receiver of an opened contract, a closed one names no address
+    fn counterparties(&self, _transaction: &Transaction) -> Vec<String> {
+        match self {
+            HtlcOperation::Open { receiver, .. } => vec![receiver.clone()],
+            HtlcOperation::Claim { .. } | HtlcOperation::Refund { .. } => vec![],
+        }
+    }
+}
+
+fn open(state: &Transition, id: &str) -> Result<Htlc, LedgerError> {
+    let htlc = state
+        .htlc_get(id)
+        .ok_or_else(|| rejected(id, "unknown contract"))?;
+    if htlc.status != HtlcStatus::Open {
+        return Err(rejected(id, "the contract is closed"));
+    }
+    Ok(htlc)
+}
+
+fn rejected(id: &str, reason: &str) -> LedgerError {
+    LedgerError::HtlcRejected {
+        htlc: id.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+impl fmt::Display for HtlcOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HtlcOperation::Open {
+                receiver,
+                amount,
+                hashlock,
+                deadline,
+            } => write!(f, "open:{}:{}:{}:{}", receiver, amount, hashlock, deadline),
+            HtlcOperation::Claim { htlc, preimage } => write!(f, "claim:{}:{}", htlc, preimage),
+            HtlcOperation::Refund { htlc } => write!(f, "refund:{}", htlc),
+        }
+    }
+}
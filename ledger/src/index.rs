@@ -0,0 +1,65 @@
+use crate::block::Block;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// where a transaction is stored in the chain
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionLocation {
+    /// the index of the block
+    pub block: u64,
+    /// the position of the transaction in the block
+    pub position: usize,
+}
+
+/// secondary indexes over the applied blocks
+/// blocks must be removed in the reverse order they were added
+pub(crate) struct Index {
+    transactions: HashMap<String, TransactionLocation>,
+    accounts: HashMap<String, Vec<TransactionLocation>>,
+}
+
+impl Index {
+    pub(crate) fn new() -> Index {
+        Index {
+            transactions: HashMap::new(),
+            accounts: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn block_add(&mut self, block: &Block) {
+        for (position, transaction) in block.transactions.iter().enumerate() {
+            let location = TransactionLocation {
+                block: block.index,
+                position,
+            };
+            self.transactions.insert(transaction.hash.clone(), location);
+            for address in transaction.addresses() {
+                self.accounts.entry(address).or_default().push(location);
+            }
+        }
+    }
+
+    pub(crate) fn block_remove(&mut self, block: &Block) {
+        for transaction in block.transactions.iter().rev() {
+            self.transactions.remove(&transaction.hash);
+            for address in transaction.addresses() {
+                if let Some(locations) = self.accounts.get_mut(&address) {
+                    locations.pop();
+                    if locations.is_empty() {
+                        self.accounts.remove(&address);
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn transaction(&self, hash: &str) -> Option<TransactionLocation> {
+        self.transactions.get(hash).copied()
+    }
+
+    pub(crate) fn account(&self, address: &str) -> &[TransactionLocation] {
+        self.accounts
+            .get(address)
+            .map_or(&[], |locations| locations)
+    }
+}
@@ -1,4 +1,16 @@
+pub mod asset;
 pub mod block;
+mod cache;
 pub mod chain;
+pub mod contract;
+pub mod error;
+pub mod event;
+pub mod executor;
+pub mod governance;
+pub mod htlc;
+pub mod index;
 mod mempool;
+pub mod multisig;
+pub mod staking;
+pub mod state;
 pub mod transaction;
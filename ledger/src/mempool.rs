@@ -1,15 +1,10 @@
+use crate::error::LedgerError;
 use crate::transaction::Transaction;
 
 pub struct Mempool {
     pool: Vec<Transaction>,
 }
 
-impl Mempool {
-    pub(crate) fn len(&self) -> u64 {
-        self.pool.len() as u64
-    }
-}
-
 impl Mempool {
     pub fn new() -> Mempool {
         Mempool {
@@ -18,11 +13,37 @@ impl Mempool {
         }
     }
 
+    pub fn len(&self) -> usize {
+        self.pool.len()
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.pool.iter().any(|t| t.hash == hash)
+    }
+
     pub fn push(&mut self, transaction: Transaction) {
         self.pool.push(transaction);
     }
 
+    pub fn remove(&mut self, hash: &str) -> Option<Transaction> {
+        let position = self.pool.iter().position(|t| t.hash == hash)?;
+        Some(self.pool.remove(position))
+    }
+
+    /// remove the transactions that can not be included at or after the block at `index` with `timestamp`
+    pub fn expire(&mut self, index: u64, timestamp: u64) -> Vec<Transaction> {
+        let (expired, pool) = self.pool.drain(..).partition(|t| {
+            matches!(
+                t.validity_check(index, timestamp),
+                Err(LedgerError::TransactionExpired { .. })
+            )
+        });
+        self.pool = pool;
+        expired
+    }
+
     pub fn drain(&mut self, n: usize) -> Vec<Transaction> {
+        let n = n.min(self.pool.len());
         self.pool.drain(0..n).collect()
     }
 }
@@ -0,0 +1,118 @@
+use crate::error::LedgerError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use utils::scheme::PublicKey;
+use utils::Utils;
+
+/// the maximum number of keys of a multisig account
+pub const MAX_MULTISIG_KEYS: usize = 16;
+
+/// an M-of-N account, spending needs `threshold` signatures by distinct `keys`
+/// the account address commits to the threshold and the keys in order, see `address`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Multisig {
+    pub threshold: usize,
+    pub keys: Vec<PublicKey>,
+}
+
+impl Multisig {
+    /// a policy from public keys of any scheme, see `PublicKey::parse`
+    pub fn new(threshold: usize, keys: &[&str]) -> Result<Multisig, LedgerError> {
+        let keys = keys
+            .iter()
+            .map(|key| PublicKey::parse(key))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| LedgerError::InvalidKey(e.to_string()))?;
+        let multisig = Multisig { threshold, keys };
+        multisig.verify()?;
+        Ok(multisig)
+    }
+
+    /// the address of the account, funds sent there are spent with this policy
+    pub fn address(&self) -> String {
+        let mut data = (self.threshold as u64).to_be_bytes().to_vec();
+        for key in &self.keys {
+            data.extend_from_slice(&key.to_bytes());
+        }
+        Utils::address_of(&data)
+    }
+
+    /// the position of a key in the policy
+    pub fn position(&self, key: &PublicKey) -> Option<usize> {
+        self.keys.iter().position(|k| k == key)
+    }
+
+    /// 1 <= threshold <= keys <= `MAX_MULTISIG_KEYS`, no key twice
+    pub fn verify(&self) -> Result<(), LedgerError> {
+        if self.keys.len() > MAX_MULTISIG_KEYS {
+            return Err(LedgerError::InvalidMultisig(format!(
+                "{} keys, at most {}",
+                self.keys.len(),
+                MAX_MULTISIG_KEYS
+            )));
+        }
+        if self.threshold == 0 || self.threshold > self.keys.len() {
+            return Err(LedgerError::InvalidMultisig(format!(
+                "threshold {} of {} keys",
+                self.threshold,
+                self.keys.len()
+            )));
+        }
+        let mut seen = HashSet::new();
+        if !self.keys.iter().all(|key| seen.insert(key.to_bytes())) {
+            return Err(LedgerError::InvalidMultisig("duplicate key".into()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::Transaction;
+
+    const KEY_PUB: &str = "MFYwEAYHKoZIzj0CAQYFK4EEAAoDQgAEUp7KSQXihfU61eVpg07CV2/8LYoWeGMl0ZhgEjCG83WLlvgeb4dQf3Ub2deTXvHoVEP8R9Nt31cdUkyc5EnqEg==";
+    const RECEIVER: &str = "crt1az98ashxv3pa5x68yrz6ve8vz0ylaey28ccmy7";
+
+    #[test]
+    fn test() {
+        let (second_priv, second_pub) = Utils::generate_keypair().unwrap();
+        let (third_priv, third_pub) = Utils::generate_keypair().unwrap();
+        assert!(matches!(
+            Multisig::new(3, &[KEY_PUB, &second_pub]),
+            Err(LedgerError::InvalidMultisig(_))
+        ));
+        assert!(matches!(
+            Multisig::new(1, &[KEY_PUB, KEY_PUB]),
+            Err(LedgerError::InvalidMultisig(_))
+        ));
+        let multisig = Multisig::new(2, &[KEY_PUB, &second_pub, &third_pub]).unwrap();
+        assert_ne!(
+            multisig.address(),
+            Multisig::new(1, &[KEY_PUB, &second_pub, &third_pub])
+                .unwrap()
+                .address()
+        );
+
+        let mut transaction = Transaction::new_multisig(1, 40, multisig.clone(), RECEIVER).unwrap();
+        assert_eq!(transaction.sender, multisig.address());
+        let outsider = Utils::generate_keypair().unwrap().0;
+        assert!(matches!(
+            transaction.sign(&Utils::get_signing_key(&outsider).unwrap()),
+            Err(LedgerError::InvalidKey(_))
+        ));
+        transaction
+            .sign(&Utils::get_signing_key(&third_priv).unwrap())
+            .unwrap();
+        assert!(matches!(
+            transaction.sign(&Utils::get_signing_key(&third_priv).unwrap()),
+            Err(LedgerError::AlreadySigned)
+        ));
+        // one of two signatures is not enough
+        assert!(!transaction.verify());
+        transaction
+            .sign(&Utils::get_signing_key(&second_priv).unwrap())
+            .unwrap();
+        assert!(transaction.verify());
+    }
+}
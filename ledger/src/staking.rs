@@ -0,0 +1,320 @@
+use crate::block::BlockHeader;
+use crate::error::LedgerError;
+use crate::executor::{operation_check, Executor};
+use crate::state::Transition;
+use crate::transaction::Transaction;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+/// the number of blocks of an epoch, the validator set only changes between epochs
+pub const EPOCH_LENGTH: u64 = 100;
+/// the number of blocks unbonded stake stays slashable before it can be withdrawn
+pub const UNBONDING_PERIOD: u64 = 2 * EPOCH_LENGTH;
+/// the maximum number of validators of an epoch
+pub const MAX_VALIDATORS: usize = 21;
+
+/// the epoch of the block at `index`
+pub fn epoch(index: u64) -> u64 {
+    index / EPOCH_LENGTH
+}
+
+/// the stake of an address
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stake {
+    /// The amount counted for the validator set
+    pub bonded: u64,
+    /// The ids of the unbondings of the address not withdrawn yet
+    pub unbonding: BTreeSet<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnbondingStatus {
+    Unbonding,
+    Withdrawn,
+    Slashed,
+}
+
+/// stake leaving the bond, still slashable until withdrawn
+/// identified by the hash of the transaction that unbonded it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Unbonding {
+    pub address: String,
+    pub amount: u64,
+    /// The index of the first block the amount can be withdrawn in
+    pub release: u64,
+    pub status: UnbondingStatus,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validator {
+    pub address: String,
+    pub stake: u64,
+}
+
+/// the validators for the stakes, the largest bonds first, at most `MAX_VALIDATORS`
+pub fn validators(stakes: &HashMap<String, Stake>) -> Vec<Validator> {
+    let mut validators: Vec<Validator> = stakes
+        .iter()
+        .filter(|(_, stake)| stake.bonded > 0)
+        .map(|(address, stake)| Validator {
+            address: address.clone(),
+            stake: stake.bonded,
+        })
+        .collect();
+    validators.sort_by(|a, b| b.stake.cmp(&a.stake).then(a.address.cmp(&b.address)));
+    validators.truncate(MAX_VALIDATORS);
+    validators
+}
+
+/// two different blocks signed by one producer at one height
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Equivocation {
+    pub first: BlockHeader,
+    pub second: BlockHeader,
+}
+
+impl Equivocation {
+    pub fn producer(&self) -> &str {
+        &self.first.producer
+    }
+
+    pub fn index(&self) -> u64 {
+        self.first.index
+    }
+
+    pub fn verify(&self) -> Result<(), LedgerError> {
+        let (first, second) = (&self.first, &self.second);
+        if first.producer != second.producer || first.index != second.index {
+            return Err(LedgerError::InvalidStaking(
+                "the blocks are not by one producer at one height".into(),
+            ));
+        }
+        if first.hash() == second.hash() {
+            return Err(LedgerError::InvalidStaking(
+                "the blocks are the same".into(),
+            ));
+        }
+        first.verify()?;
+        second.verify()
+    }
+}
+
+/// the stake an equivocation burnt, kept to revert it
+#[derive(Clone, Debug)]
+pub(crate) struct Slash {
+    bonded: u64,
+    unbonding: BTreeSet<String>,
+}
+
+/// a staking operation, in place of a transfer
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StakingOperation {
+    /// lock an amount of the native coin of the sender as stake
+    Bond { amount: u64 },
+    /// start unbonding stake, withdrawable after `UNBONDING_PERIOD` blocks
+    Unbond { amount: u64 },
+    /// pay a released unbonding back to its address
+    Withdraw { unbonding: String },
+    /// burn the bonded and unbonding stake of the producer, by anyone holding the evidence
+    /// the producer was a validator of the epoch and the evidence is at most `UNBONDING_PERIOD` blocks old
+    Slash { evidence: Box<Equivocation> },
+}
+
+impl Executor for StakingOperation {
+    fn verify(&self, transaction: &Transaction) -> Result<(), LedgerError> {
+        operation_check(transaction)?;
+        match self {
+            StakingOperation::Bond { amount } | StakingOperation::Unbond { amount } => {
+                if *amount == 0 {
+                    return Err(LedgerError::InvalidStaking("no amount".into()));
+                }
+                Ok(())
+            }
+            StakingOperation::Withdraw { .. } => Ok(()),
+            StakingOperation::Slash { evidence } => evidence.verify(),
+        }
+    }
+
+    fn apply(&self, state: &mut Transition, transaction: &Transaction) -> Result<(), LedgerError> {
+        let sender = &transaction.sender;
+        match self {
+            StakingOperation::Bond { amount } => {
+                state.debit(sender, &None, *amount)?;
+                let mut stake = state.stake_get(sender);
+                stake.bonded = stake.bonded.checked_add(*amount).ok_or_else(|| {
+                    LedgerError::BalanceOverflow {
+                        address: sender.clone(),
+                        balance: stake.bonded,
+                        amount: *amount,
+                    }
+                })?;
+                state.stake_set(sender, stake);
+            }
+            StakingOperation::Unbond { amount } => {
+                let mut stake = state.stake_get(sender);
+                stake.bonded = stake
+                    .bonded
+                    .checked_sub(*amount)
+                    .ok_or_else(|| rejected(sender, "unbonding more than bonded"))?;
+                let unbonding = Unbonding {
+                    address: sender.clone(),
+                    amount: *amount,
+                    release: state.index().saturating_add(UNBONDING_PERIOD),
+                    status: UnbondingStatus::Unbonding,
+                };
+                state.unbonding_set(&transaction.hash, Some(unbonding));
+                stake.unbonding.insert(transaction.hash.clone());
+                state.stake_set(sender, stake);
+            }
+            StakingOperation::Withdraw { unbonding: id } => {
+                let mut unbonding = state
+                    .unbonding_get(id)
+                    .ok_or_else(|| rejected(sender, "unknown unbonding"))?;
+                if &unbonding.address != sender {
+                    return Err(rejected(sender, "not the unbonding address"));
+                }
+                if unbonding.status != UnbondingStatus::Unbonding {
+                    return Err(rejected(sender, "the unbonding is closed"));
+                }
+                if state.index() < unbonding.release {
+                    return Err(rejected(sender, "the unbonding is not released"));
+                }
+                state.credit(sender, &None, unbonding.amount)?;
+                unbonding.status = UnbondingStatus::Withdrawn;
+                state.unbonding_set(id, Some(unbonding));
+                let mut stake = state.stake_get(sender);
+                stake.unbonding.remove(id);
+                state.stake_set(sender, stake);
+            }
+            StakingOperation::Slash { evidence } => {
+                let producer = evidence.producer();
+                // the stake unbonded since may have been withdrawn
+                if state.index() > evidence.index().saturating_add(UNBONDING_PERIOD) {
+                    return Err(rejected(
+                        producer,
+                        "evidence older than the unbonding period",
+                    ));
+                }
+                let validators = state.validators_get(epoch(evidence.index()));
+                let validator = validators
+                    .is_some_and(|validators| validators.iter().any(|v| v.address == producer));
+                if !validator {
+                    return Err(rejected(producer, "not a validator of the epoch"));
+                }
+                if state.slash_get(producer, evidence.index()).is_some() {
+                    return Err(rejected(producer, "already slashed at this height"));
+                }
+                let stake = state.stake_get(producer);
+                if stake == Stake::default() {
+                    return Err(rejected(producer, "no stake"));
+                }
+                unbonding_status(state, &stake.unbonding, UnbondingStatus::Slashed)?;
+                let slash = Slash {
+                    bonded: stake.bonded,
+                    unbonding: stake.unbonding,
+                };
+                state.slash_set(producer, evidence.index(), Some(slash));
+                state.stake_set(producer, Stake::default());
+            }
+        }
+        Ok(())
+    }
+
+    fn revert(&self, state: &mut Transition, transaction: &Transaction) -> Result<(), LedgerError> {
+        let sender = &transaction.sender;
+        match self {
+            StakingOperation::Bond { amount } => {
+                let mut stake = state.stake_get(sender);
+                stake.bonded = stake
+                    .bonded
+                    .checked_sub(*amount)
+                    .ok_or_else(|| rejected(sender, "unbonding more than bonded"))?;
+                state.stake_set(sender, stake);
+                state.credit(sender, &None, *amount)?;
+            }
+            StakingOperation::Unbond { amount } => {
+                state.unbonding_set(&transaction.hash, None);
+                let mut stake = state.stake_get(sender);
+                stake.unbonding.remove(&transaction.hash);
+                stake.bonded = stake.bonded.saturating_add(*amount);
+                state.stake_set(sender, stake);
+            }
+            StakingOperation::Withdraw { unbonding: id } => {
+                let mut unbonding = state
+                    .unbonding_get(id)
+                    .ok_or_else(|| rejected(sender, "unknown unbonding"))?;
+                state.debit(sender, &None, unbonding.amount)?;
+                unbonding.status = UnbondingStatus::Unbonding;
+                state.unbonding_set(id, Some(unbonding));
+                let mut stake = state.stake_get(sender);
+                stake.unbonding.insert(id.clone());
+                state.stake_set(sender, stake);
+            }
+            StakingOperation::Slash { evidence } => {
+                let producer = evidence.producer();
+                let slash = state
+                    .slash_get(producer, evidence.index())
+                    .ok_or_else(|| rejected(producer, "not slashed at this height"))?;
+                unbonding_status(state, &slash.unbonding, UnbondingStatus::Unbonding)?;
+                let stake = Stake {
+                    bonded: slash.bonded,
+                    unbonding: slash.unbonding,
+                };
+                state.stake_set(producer, stake);
+                state.slash_set(producer, evidence.index(), None);
+            }
+        }
+        Ok(())
+    }
+
+    /// the slashed producer
+    fn counterparties(&self, _transaction: &Transaction) -> Vec<String> {
+        match self {
+            StakingOperation::Slash { evidence } => vec![evidence.producer().to_string()],
+            _ => vec![],
+        }
+    }
+}
+
+fn unbonding_status(
+    state: &mut Transition,
+    ids: &BTreeSet<String>,
+    status: UnbondingStatus,
+) -> Result<(), LedgerError> {
+    for id in ids {
+        let mut unbonding = state
+            .unbonding_get(id)
+            .ok_or_else(|| LedgerError::InvalidStaking(format!("unknown unbonding {}", id)))?;
+        unbonding.status = status;
+        state.unbonding_set(id, Some(unbonding));
+    }
+    Ok(())
+}
+
+fn rejected(address: &str, reason: &str) -> LedgerError {
+    LedgerError::StakingRejected {
+        address: address.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+impl fmt::Display for StakingOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StakingOperation::Bond { amount } => write!(f, "bond:{}", amount),
+            StakingOperation::Unbond { amount } => write!(f, "unbond:{}", amount),
+            StakingOperation::Withdraw { unbonding } => write!(f, "withdraw:{}", unbonding),
+            StakingOperation::Slash { evidence } => write!(
+                f,
+                "slash:{}:{}:{}:{}",
+                evidence.first.hash(),
+                evidence.first.signature,
+                evidence.second.hash(),
+                evidence.second.signature
+            ),
+        }
+    }
+}
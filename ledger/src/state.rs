@@ -0,0 +1,460 @@
+use crate::asset::Asset;
+use crate::contract::{Contract, Receipt};
+use crate::error::LedgerError;
+use crate::governance::{self, Control};
+use crate::htlc::Htlc;
+use crate::staking::{Slash, Stake, Unbonding, Validator};
+use crate::transaction::Transaction;
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// an account balance in the native coin, or in the asset with the id
+pub(crate) type BalanceKey = (String, Option<String>);
+/// a key in the storage of the contract with the address
+pub(crate) type StorageKey = (String, Vec<u8>);
+/// a producer slashed for an equivocation at the block index
+pub(crate) type SlashKey = (String, u64);
+/// the native coins an account with a limit spent on the day
+pub(crate) type SpendingKey = (String, u64);
+
+/// the account state of the chain
+#[derive(Default)]
+pub(crate) struct State {
+    pub(crate) balance: HashMap<BalanceKey, u64>,
+    pub(crate) nonce: HashMap<String, u64>,
+    /// the issued assets by id
+    pub(crate) assets: HashMap<String, Asset>,
+    /// the contracts by id, closed ones included
+    pub(crate) htlcs: HashMap<String, Htlc>,
+    /// the smart contracts by address
+    pub(crate) contracts: HashMap<String, Contract>,
+    pub(crate) storage: HashMap<StorageKey, Vec<u8>>,
+    /// the smart contract calls by transaction hash
+    pub(crate) receipts: HashMap<String, Receipt>,
+    /// the stakes by address, none bonded or unbonding left out
+    pub(crate) stakes: HashMap<String, Stake>,
+    /// the unbondings by the hash of the unbond transaction, closed ones included
+    pub(crate) unbondings: HashMap<String, Unbonding>,
+    pub(crate) slashes: HashMap<SlashKey, Slash>,
+    /// the validators of each epoch, from the stakes before its first block
+    pub(crate) validators: HashMap<u64, Vec<Validator>>,
+    /// the address named by the genesis block, none for a chain without governance
+    pub(crate) governance: Option<String>,
+    /// the account controls by address, accounts without any left out
+    pub(crate) controls: HashMap<String, Control>,
+    /// the control replaced by the governance transaction with the hash
+    pub(crate) control_undo: HashMap<String, Control>,
+    pub(crate) spending: HashMap<SpendingKey, u64>,
+}
+
+impl State {
+    pub(crate) fn commit(&mut self, changes: StateChanges) {
+        self.balance.extend(changes.balance);
+        self.nonce.extend(changes.nonce);
+        commit(&mut self.assets, changes.assets);
+        commit(&mut self.htlcs, changes.htlcs);
+        commit(&mut self.contracts, changes.contracts);
+        commit(&mut self.storage, changes.storage);
+        commit(&mut self.receipts, changes.receipts);
+        commit(&mut self.stakes, changes.stakes);
+        commit(&mut self.unbondings, changes.unbondings);
+        commit(&mut self.slashes, changes.slashes);
+        commit(&mut self.controls, changes.controls);
+        commit(&mut self.control_undo, changes.control_undo);
+        commit(&mut self.spending, changes.spending);
+    }
+}
+
+/// the state a block changes, a removed entry is none
+#[derive(Default)]
+pub(crate) struct StateChanges {
+    pub(crate) balance: HashMap<BalanceKey, u64>,
+    pub(crate) nonce: HashMap<String, u64>,
+    pub(crate) assets: HashMap<String, Option<Asset>>,
+    pub(crate) htlcs: HashMap<String, Option<Htlc>>,
+    pub(crate) contracts: HashMap<String, Option<Contract>>,
+    pub(crate) storage: HashMap<StorageKey, Option<Vec<u8>>>,
+    pub(crate) receipts: HashMap<String, Option<Receipt>>,
+    pub(crate) stakes: HashMap<String, Option<Stake>>,
+    pub(crate) unbondings: HashMap<String, Option<Unbonding>>,
+    pub(crate) slashes: HashMap<SlashKey, Option<Slash>>,
+    pub(crate) controls: HashMap<String, Option<Control>>,
+    pub(crate) control_undo: HashMap<String, Option<Control>>,
+    pub(crate) spending: HashMap<SpendingKey, Option<u64>>,
+}
+
+/// the changes of the transaction being applied, committed to the transition if it succeeds
+#[derive(Default)]
+struct Pending {
+    balance: HashMap<BalanceKey, u64>,
+    assets: HashMap<String, Option<Asset>>,
+    htlcs: HashMap<String, Option<Htlc>>,
+    contracts: HashMap<String, Option<Contract>>,
+    storage: HashMap<StorageKey, Option<Vec<u8>>>,
+    receipts: HashMap<String, Option<Receipt>>,
+    stakes: HashMap<String, Option<Stake>>,
+    unbondings: HashMap<String, Option<Unbonding>>,
+    slashes: HashMap<SlashKey, Option<Slash>>,
+    controls: HashMap<String, Option<Control>>,
+    control_undo: HashMap<String, Option<Control>>,
+    spending: HashMap<SpendingKey, Option<u64>>,
+}
+
+/// transactions of the block at `index` with `timestamp` applied one after the other
+/// on top of the chain state, with checked arithmetic
+/// a transaction that fails leaves the transition as it was
+/// the state interface of an `Executor`, reads see the writes of the transaction so far
+pub struct Transition<'a> {
+    state: &'a State,
+    index: u64,
+    timestamp: u64,
+    changes: StateChanges,
+    pending: Pending,
+}
+
+impl<'a> Transition<'a> {
+    pub(crate) fn new(state: &'a State, index: u64, timestamp: u64) -> Transition<'a> {
+        Transition {
+            state,
+            index,
+            timestamp,
+            changes: StateChanges::default(),
+            pending: Pending::default(),
+        }
+    }
+
+    /// the index of the block the transactions are in
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// the timestamp of the block the transactions are in
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn balance_get(&self, address: &str, asset: &Option<String>) -> u64 {
+        let key = (address.to_string(), asset.clone());
+        self.pending
+            .balance
+            .get(&key)
+            .or_else(|| self.changes.balance.get(&key))
+            .or_else(|| self.state.balance.get(&key))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn nonce_get(&self, sender: &str) -> u64 {
+        self.changes
+            .nonce
+            .get(sender)
+            .or_else(|| self.state.nonce.get(sender))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn asset_get(&self, id: &str) -> Result<Asset, LedgerError> {
+        get(
+            &self.pending.assets,
+            &self.changes.assets,
+            &self.state.assets,
+            id,
+        )
+        .ok_or_else(|| LedgerError::UnknownAsset(id.to_string()))
+    }
+
+    /// create or update the asset with the id, remove it if none
+    pub fn asset_set(&mut self, id: &str, asset: Option<Asset>) {
+        self.pending.assets.insert(id.to_string(), asset);
+    }
+
+    pub fn htlc_get(&self, id: &str) -> Option<Htlc> {
+        get(
+            &self.pending.htlcs,
+            &self.changes.htlcs,
+            &self.state.htlcs,
+            id,
+        )
+    }
+
+    /// open or update the contract with the id, remove it if none
+    pub fn htlc_set(&mut self, id: &str, htlc: Option<Htlc>) {
+        self.pending.htlcs.insert(id.to_string(), htlc);
+    }
+
+    pub fn contract_get(&self, address: &str) -> Option<Contract> {
+        let (pending, changes) = (&self.pending.contracts, &self.changes.contracts);
+        get(pending, changes, &self.state.contracts, address)
+    }
+
+    /// deploy the contract at the address, remove it if none
+    pub fn contract_set(&mut self, address: &str, contract: Option<Contract>) {
+        self.pending.contracts.insert(address.to_string(), contract);
+    }
+
+    pub fn storage_get(&self, contract: &str, key: &[u8]) -> Option<Vec<u8>> {
+        let key = (contract.to_string(), key.to_vec());
+        let (pending, changes) = (&self.pending.storage, &self.changes.storage);
+        get(pending, changes, &self.state.storage, &key)
+    }
+
+    /// write the key of the contract storage, remove it if none
+    pub fn storage_set(&mut self, contract: &str, key: &[u8], value: Option<Vec<u8>>) {
+        let key = (contract.to_string(), key.to_vec());
+        self.pending.storage.insert(key, value);
+    }
+
+    pub fn receipt_get(&self, hash: &str) -> Option<Receipt> {
+        let (pending, changes) = (&self.pending.receipts, &self.changes.receipts);
+        get(pending, changes, &self.state.receipts, hash)
+    }
+
+    /// record the call of the transaction with the hash, forget it if none
+    pub fn receipt_set(&mut self, hash: &str, receipt: Option<Receipt>) {
+        self.pending.receipts.insert(hash.to_string(), receipt);
+    }
+
+    /// the validators of the epoch, the set of a block's epoch is taken after its first block
+    pub fn validators_get(&self, epoch: u64) -> Option<&Vec<Validator>> {
+        self.state.validators.get(&epoch)
+    }
+
+    pub fn stake_get(&self, address: &str) -> Stake {
+        let (pending, changes) = (&self.pending.stakes, &self.changes.stakes);
+        get(pending, changes, &self.state.stakes, address).unwrap_or_default()
+    }
+
+    /// update the stake of the address, an empty stake is removed
+    pub fn stake_set(&mut self, address: &str, stake: Stake) {
+        let stake = Some(stake).filter(|stake| *stake != Stake::default());
+        self.pending.stakes.insert(address.to_string(), stake);
+    }
+
+    pub fn unbonding_get(&self, id: &str) -> Option<Unbonding> {
+        let (pending, changes) = (&self.pending.unbondings, &self.changes.unbondings);
+        get(pending, changes, &self.state.unbondings, id)
+    }
+
+    /// start or update the unbonding with the id, remove it if none
+    pub fn unbonding_set(&mut self, id: &str, unbonding: Option<Unbonding>) {
+        self.pending.unbondings.insert(id.to_string(), unbonding);
+    }
+
+    pub(crate) fn slash_get(&self, producer: &str, index: u64) -> Option<Slash> {
+        let key = (producer.to_string(), index);
+        let (pending, changes) = (&self.pending.slashes, &self.changes.slashes);
+        get(pending, changes, &self.state.slashes, &key)
+    }
+
+    pub(crate) fn slash_set(&mut self, producer: &str, index: u64, slash: Option<Slash>) {
+        let key = (producer.to_string(), index);
+        self.pending.slashes.insert(key, slash);
+    }
+
+    /// the address allowed to send governance transactions
+    pub fn governance(&self) -> Option<&str> {
+        self.state.governance.as_deref()
+    }
+
+    pub fn control_get(&self, address: &str) -> Control {
+        let (pending, changes) = (&self.pending.controls, &self.changes.controls);
+        get(pending, changes, &self.state.controls, address).unwrap_or_default()
+    }
+
+    /// update the controls of the address, no controls are removed
+    pub fn control_set(&mut self, address: &str, control: Control) {
+        let control = Some(control).filter(|control| *control != Control::default());
+        self.pending.controls.insert(address.to_string(), control);
+    }
+
+    pub(crate) fn control_undo_get(&self, hash: &str) -> Option<Control> {
+        let (pending, changes) = (&self.pending.control_undo, &self.changes.control_undo);
+        get(pending, changes, &self.state.control_undo, hash)
+    }
+
+    pub(crate) fn control_undo_set(&mut self, hash: &str, control: Option<Control>) {
+        self.pending.control_undo.insert(hash.to_string(), control);
+    }
+
+    /// the native coins the address spent on the day of the block
+    fn spending_get(&self, address: &str) -> u64 {
+        let key = (address.to_string(), governance::day(self.timestamp));
+        let (pending, changes) = (&self.pending.spending, &self.changes.spending);
+        get(pending, changes, &self.state.spending, &key).unwrap_or(0)
+    }
+
+    fn spending_set(&mut self, address: &str, spent: u64) {
+        let key = (address.to_string(), governance::day(self.timestamp));
+        self.pending
+            .spending
+            .insert(key, Some(spent).filter(|spent| *spent > 0));
+    }
+
+    /// count what the sender of the transaction spent, from its native balance `before`,
+    /// against its daily limit
+    fn spend(&mut self, sender: &str, before: u64, limit: Option<u64>) -> Result<(), LedgerError> {
+        let amount = before.saturating_sub(self.balance_get(sender, &None));
+        if limit.is_none() || amount == 0 {
+            return Ok(());
+        }
+        let spent = governance::limit_check(sender, limit, self.spending_get(sender), amount)?;
+        self.spending_set(sender, spent);
+        Ok(())
+    }
+
+    /// undo `spend`, from the native balance of the sender `before` the revert
+    fn unspend(&mut self, sender: &str, before: u64) {
+        let amount = self.balance_get(sender, &None).saturating_sub(before);
+        if self.control_get(sender).limit.is_none() || amount == 0 {
+            return;
+        }
+        let spent = self.spending_get(sender).saturating_sub(amount);
+        self.spending_set(sender, spent);
+    }
+
+    /// check the nonce and the account controls, then carry out the transaction with the
+    /// executor of its kind
+    pub(crate) fn apply(&mut self, transaction: &Transaction) -> Result<(), LedgerError> {
+        let sender = &transaction.sender;
+        let last_known_nonce = self.nonce_get(sender);
+        if last_known_nonce.checked_add(1) != Some(transaction.nonce) {
+            return Err(LedgerError::InvalidNonce {
+                sender: sender.clone(),
+                expected: last_known_nonce.saturating_add(1),
+                got: transaction.nonce,
+            });
+        }
+        let control = self.control_get(sender);
+        // the governance can not lock itself out
+        if control.frozen && self.governance() != Some(sender.as_str()) {
+            return Err(LedgerError::AccountFrozen {
+                address: sender.clone(),
+            });
+        }
+
+        let kind = transaction.kind().ok_or_else(|| invalid(transaction))?;
+        let before = self.balance_get(sender, &None);
+        let result = kind
+            .executor()
+            .apply(self, transaction)
+            .and_then(|()| self.spend(sender, before, control.limit));
+        self.commit(result)?;
+        self.changes.nonce.insert(sender.clone(), transaction.nonce);
+        Ok(())
+    }
+
+    /// undo `apply`, transactions are reverted in the reverse order they were applied
+    pub(crate) fn revert(&mut self, transaction: &Transaction) -> Result<(), LedgerError> {
+        let kind = transaction.kind().ok_or_else(|| invalid(transaction))?;
+        let before = self.balance_get(&transaction.sender, &None);
+        let result = kind.executor().revert(self, transaction);
+        if result.is_ok() {
+            self.unspend(&transaction.sender, before);
+        }
+        self.commit(result)?;
+        self.changes.nonce.insert(
+            transaction.sender.clone(),
+            transaction.nonce.saturating_sub(1),
+        );
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> StateChanges {
+        self.changes
+    }
+
+    pub fn debit(
+        &mut self,
+        address: &str,
+        asset: &Option<String>,
+        amount: u64,
+    ) -> Result<(), LedgerError> {
+        let current = self.balance_get(address, asset);
+        let debited =
+            current
+                .checked_sub(amount)
+                .ok_or_else(|| LedgerError::InsufficientBalance {
+                    sender: address.to_string(),
+                    balance: current,
+                    amount,
+                })?;
+        self.pending
+            .balance
+            .insert((address.to_string(), asset.clone()), debited);
+        Ok(())
+    }
+
+    pub fn credit(
+        &mut self,
+        address: &str,
+        asset: &Option<String>,
+        amount: u64,
+    ) -> Result<(), LedgerError> {
+        let current = self.balance_get(address, asset);
+        let credited = current
+            .checked_add(amount)
+            .ok_or_else(|| LedgerError::BalanceOverflow {
+                address: address.to_string(),
+                balance: current,
+                amount,
+            })?;
+        self.pending
+            .balance
+            .insert((address.to_string(), asset.clone()), credited);
+        Ok(())
+    }
+
+    /// keep the pending changes if the transaction succeeded, drop them otherwise
+    fn commit(&mut self, result: Result<(), LedgerError>) -> Result<(), LedgerError> {
+        let pending = std::mem::take(&mut self.pending);
+        result?;
+        self.changes.balance.extend(pending.balance);
+        self.changes.assets.extend(pending.assets);
+        self.changes.htlcs.extend(pending.htlcs);
+        self.changes.contracts.extend(pending.contracts);
+        self.changes.storage.extend(pending.storage);
+        self.changes.receipts.extend(pending.receipts);
+        self.changes.stakes.extend(pending.stakes);
+        self.changes.unbondings.extend(pending.unbondings);
+        self.changes.slashes.extend(pending.slashes);
+        self.changes.controls.extend(pending.controls);
+        self.changes.control_undo.extend(pending.control_undo);
+        self.changes.spending.extend(pending.spending);
+        Ok(())
+    }
+}
+
+/// the latest value, none if it was removed or never set
+fn get<K, Q, V>(
+    pending: &HashMap<K, Option<V>>,
+    changes: &HashMap<K, Option<V>>,
+    state: &HashMap<K, V>,
+    key: &Q,
+) -> Option<V>
+where
+    K: Borrow<Q> + Eq + Hash,
+    Q: Eq + Hash + ?Sized,
+    V: Clone,
+{
+    match pending.get(key).or_else(|| changes.get(key)) {
+        Some(value) => value.clone(),
+        None => state.get(key).cloned(),
+    }
+}
+
+fn commit<K: Eq + Hash, V>(state: &mut HashMap<K, V>, changes: HashMap<K, Option<V>>) {
+    for (key, value) in changes {
+        match value {
+            Some(value) => state.insert(key, value),
+            None => state.remove(&key),
+        };
+    }
+}
+
+fn invalid(transaction: &Transaction) -> LedgerError {
+    LedgerError::InvalidTransaction {
+        sender: transaction.sender.clone(),
+        nonce: transaction.nonce,
+    }
+}
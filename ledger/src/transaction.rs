@@ -1,76 +1,825 @@
-use k256::ecdsa::{Signature, SigningKey, VerifyingKey};
+use crate::asset::AssetOperation;
+use crate::contract::ContractOperation;
+use crate::error::LedgerError;
+use crate::executor::TransactionKind;
+use crate::governance::GovernanceOperation;
+use crate::htlc::HtlcOperation;
+use crate::multisig::Multisig;
+use crate::staking::StakingOperation;
+use k256::ecdsa::SigningKey;
 use serde::{Deserialize, Serialize};
-use utils::Utils;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use utils::scheme::{PrivateKey, PublicKey, Scheme, Signature};
+use utils::{SigningMode, Utils};
 
-#[derive(Serialize, Deserialize)]
+/// the maximum number of bytes of a memo
+pub const MAX_MEMO_SIZE: usize = 256;
+/// the maximum number of outputs of a batch transfer
+pub const MAX_OUTPUTS: usize = 256;
+
+/// who may spend from the sender address
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Authority {
+    /// a single key of any scheme, the address is `PublicKey::address`
+    Key(PublicKey),
+    /// M-of-N keys, the address is `Multisig::address`
+    Multisig(Multisig),
+}
+
+impl Authority {
+    pub fn address(&self) -> String {
+        match self {
+            Authority::Key(key) => key.address(),
+            Authority::Multisig(multisig) => multisig.address(),
+        }
+    }
+
+    fn keys(&self) -> &[PublicKey] {
+        match self {
+            Authority::Key(key) => std::slice::from_ref(key),
+            Authority::Multisig(multisig) => &multisig.keys,
+        }
+    }
+
+    fn threshold(&self) -> usize {
+        match self {
+            Authority::Key(_) => 1,
+            Authority::Multisig(multisig) => multisig.threshold,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Authority::Key(key) => key.to_bytes().len(),
+            Authority::Multisig(multisig) => {
+                8 + multisig
+                    .keys
+                    .iter()
+                    .map(|key| key.to_bytes().len())
+                    .sum::<usize>()
+            }
+        }
+    }
+}
+
+/// a point on the chain, before or after which a transaction is not valid
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Lock {
+    /// a block index
+    Height(u64),
+    /// a block timestamp, unix seconds
+    Time(u64),
+}
+
+impl Lock {
+    /// the block at `index` with `timestamp` is at or past the lock
+    pub fn reached(&self, index: u64, timestamp: u64) -> bool {
+        match *self {
+            Lock::Height(height) => index >= height,
+            Lock::Time(time) => timestamp >= time,
+        }
+    }
+
+    /// the block at `index` with `timestamp` is strictly past the lock
+    pub fn passed(&self, index: u64, timestamp: u64) -> bool {
+        match *self {
+            Lock::Height(height) => index > height,
+            Lock::Time(time) => timestamp > time,
+        }
+    }
+}
+
+impl fmt::Display for Lock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lock::Height(height) => write!(f, "height:{}", height),
+            Lock::Time(time) => write!(f, "time:{}", time),
+        }
+    }
+}
+
+impl FromStr for Lock {
+    type Err = String;
+
+    /// `height:<index>` or `time:<unix seconds>`
+    fn from_str(lock: &str) -> Result<Lock, String> {
+        let invalid = || format!("expected height:<index> or time:<seconds>, got {}", lock);
+        let (kind, value) = lock.split_once(':').ok_or_else(invalid)?;
+        let value = value.parse().map_err(|_| invalid())?;
+        match kind {
+            "height" => Ok(Lock::Height(value)),
+            "time" => Ok(Lock::Time(value)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// data attached to a transfer, e.g. an invoice reference
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Memo {
+    Text(String),
+    /// hex encoded in JSON
+    Binary(#[serde(with = "hex::serde")] Vec<u8>),
+}
+
+impl Memo {
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Memo::Text(text) => text.as_bytes(),
+            Memo::Binary(data) => data,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.as_bytes().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.as_bytes().is_empty()
+    }
+}
+
+impl fmt::Display for Memo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Memo::Text(text) => write!(f, "text:{}", text),
+            Memo::Binary(data) => write!(f, "binary:{}", hex::encode(data)),
+        }
+    }
+}
+
+/// a receiver of a batch transfer and the amount it is credited
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Output {
+    pub receiver: String,
+    pub amount: u64,
+}
+
+/// a signature by the authority key at position `key`, hex encoded in the scheme of the key
+#[derive(Clone, Serialize, Deserialize)]
+struct KeySignature {
+    key: usize,
+    encoded: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Transaction {
     pub nonce: u64,
     pub(crate) amount: u64,
+    /// The sender address, see `Authority::address`
     pub sender: String,
-    sender_key: VerifyingKey,
+    /// The sender public key or multisig policy, it must hash to the sender address
+    authority: Authority,
+    /// The receiver address, empty for a batch transfer
     pub receiver: String,
-    receiver_key: VerifyingKey,
+    /// The receivers of a batch transfer, in place of `receiver` and `amount`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<Output>,
+    /// The id of the asset transferred, the native coin if none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset: Option<String>,
+    /// An asset operation, in place of a transfer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation: Option<AssetOperation>,
+    /// A contract operation, in place of a transfer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub htlc: Option<HtlcOperation>,
+    /// A smart contract operation, in place of a transfer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contract: Option<ContractOperation>,
+    /// A staking operation, in place of a transfer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub staking: Option<StakingOperation>,
+    /// A governance control, in place of a transfer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub governance: Option<GovernanceOperation>,
+    /// The transaction is not valid in blocks before this one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_after: Option<Lock>,
+    /// The transaction is not valid in blocks after this one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_until: Option<Lock>,
+    /// At most `MAX_MEMO_SIZE` bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<Memo>,
     pub(crate) hash: String,
-    signature: Option<(String, Signature)>,
+    signatures: Vec<KeySignature>,
 }
 
 impl Transaction {
+    /// a transfer from the owner of `sender_key`, a public key of any scheme, to the `receiver` address
     pub fn new(
         nonce: u64,
         amount: u64,
-        sender: &str,
+        sender_key: &str,
         receiver: &str,
         signature: Option<&str>,
-    ) -> Result<Transaction, Box<dyn std::error::Error>> {
-        let hash = Transaction::calculate_hash(nonce, sender, receiver, amount);
-        Ok(Transaction {
+    ) -> Result<Transaction, LedgerError> {
+        let sender_key =
+            PublicKey::parse(sender_key).map_err(|e| LedgerError::InvalidKey(e.to_string()))?;
+        let mut transaction =
+            Transaction::with_authority(nonce, amount, Authority::Key(sender_key), receiver)?;
+        if let Some(signature) = signature {
+            transaction.signature_add(0, signature)?;
+        }
+        Ok(transaction)
+    }
+
+    /// an unsigned transfer from the multisig account, signed by its holders with `sign`
+    pub fn new_multisig(
+        nonce: u64,
+        amount: u64,
+        multisig: Multisig,
+        receiver: &str,
+    ) -> Result<Transaction, LedgerError> {
+        multisig.verify()?;
+        Transaction::with_authority(nonce, amount, Authority::Multisig(multisig), receiver)
+    }
+
+    /// a transfer to several receivers under one nonce and signature
+    /// the sender must afford the total of the outputs
+    pub fn new_batch(
+        nonce: u64,
+        sender_key: &str,
+        outputs: Vec<Output>,
+    ) -> Result<Transaction, LedgerError> {
+        let sender_key =
+            PublicKey::parse(sender_key).map_err(|e| LedgerError::InvalidKey(e.to_string()))?;
+        Transaction::with_outputs(nonce, Authority::Key(sender_key), outputs)
+    }
+
+    /// an unsigned batch transfer from the multisig account
+    pub fn new_batch_multisig(
+        nonce: u64,
+        multisig: Multisig,
+        outputs: Vec<Output>,
+    ) -> Result<Transaction, LedgerError> {
+        multisig.verify()?;
+        Transaction::with_outputs(nonce, Authority::Multisig(multisig), outputs)
+    }
+
+    /// an asset operation by the owner of `sender_key`, an issued asset is identified by
+    /// the hash of the transaction
+    pub fn new_asset(
+        nonce: u64,
+        sender_key: &str,
+        operation: AssetOperation,
+    ) -> Result<Transaction, LedgerError> {
+        Transaction::with_operation(nonce, sender_key, |transaction| {
+            transaction.operation = Some(operation)
+        })
+    }
+
+    /// a contract operation by the owner of `sender_key`, an opened contract is identified by
+    /// the hash of the transaction
+    pub fn new_htlc(
+        nonce: u64,
+        sender_key: &str,
+        operation: HtlcOperation,
+    ) -> Result<Transaction, LedgerError> {
+        Transaction::with_operation(nonce, sender_key, |transaction| {
+            transaction.htlc = Some(operation)
+        })
+    }
+
+    /// a smart contract operation by the owner of `sender_key`, a deployed contract is at
+    /// `contract_address` of the transaction hash
+    pub fn new_contract(
+        nonce: u64,
+        sender_key: &str,
+        operation: ContractOperation,
+    ) -> Result<Transaction, LedgerError> {
+        Transaction::with_operation(nonce, sender_key, |transaction| {
+            transaction.contract = Some(operation)
+        })
+    }
+
+    /// a staking operation by the owner of `sender_key`, an unbonding is identified by
+    /// the transaction hash
+    pub fn new_staking(
+        nonce: u64,
+        sender_key: &str,
+        operation: StakingOperation,
+    ) -> Result<Transaction, LedgerError> {
+        Transaction::with_operation(nonce, sender_key, |transaction| {
+            transaction.staking = Some(operation)
+        })
+    }
+
+    /// a governance control by the owner of `sender_key`, applied only if it is the key of
+    /// the governance address
+    pub fn new_governance(
+        nonce: u64,
+        sender_key: &str,
+        operation: GovernanceOperation,
+    ) -> Result<Transaction, LedgerError> {
+        Transaction::with_operation(nonce, sender_key, |transaction| {
+            transaction.governance = Some(operation)
+        })
+    }
+
+    /// an operation in place of a transfer by the owner of `sender_key`, `set` puts it in
+    /// its field
+    fn with_operation(
+        nonce: u64,
+        sender_key: &str,
+        set: impl FnOnce(&mut Transaction),
+    ) -> Result<Transaction, LedgerError> {
+        let sender_key =
+            PublicKey::parse(sender_key).map_err(|e| LedgerError::InvalidKey(e.to_string()))?;
+        let mut transaction =
+            Transaction::build(nonce, 0, Authority::Key(sender_key), String::new(), vec![]);
+        set(&mut transaction);
+        transaction.kind_check()?;
+        transaction.hash = transaction.calculate_hash();
+        Ok(transaction)
+    }
+
+    fn with_authority(
+        nonce: u64,
+        amount: u64,
+        authority: Authority,
+        receiver: &str,
+    ) -> Result<Transaction, LedgerError> {
+        address_check(receiver)?;
+        Ok(Transaction::build(
             nonce,
             amount,
-            sender: sender.to_string(),
-            sender_key: Utils::get_verifying_key(sender)?,
-            receiver: receiver.to_string(),
-            receiver_key: Utils::get_verifying_key(receiver)?,
-            hash,
-            signature: match signature {
-                None => None,
-                Some(signature) => {
-                    Some((signature.to_string(), Utils::decode_signature(signature)?))
-                }
-            },
-        })
+            authority,
+            receiver.to_string(),
+            vec![],
+        ))
+    }
+
+    fn with_outputs(
+        nonce: u64,
+        authority: Authority,
+        outputs: Vec<Output>,
+    ) -> Result<Transaction, LedgerError> {
+        if outputs.is_empty() || outputs.len() > MAX_OUTPUTS {
+            return Err(LedgerError::InvalidOutputs(format!(
+                "{} outputs, expected 1 to {}",
+                outputs.len(),
+                MAX_OUTPUTS
+            )));
+        }
+        for output in &outputs {
+            address_check(&output.receiver)?;
+        }
+        let transaction = Transaction::build(nonce, 0, authority, String::new(), outputs);
+        if transaction.total().is_none() {
+            return Err(LedgerError::InvalidOutputs(
+                "the total amount overflows".into(),
+            ));
+        }
+        Ok(transaction)
+    }
+
+    fn build(
+        nonce: u64,
+        amount: u64,
+        authority: Authority,
+        receiver: String,
+        outputs: Vec<Output>,
+    ) -> Transaction {
+        let mut transaction = Transaction {
+            nonce,
+            amount,
+            sender: authority.address(),
+            authority,
+            receiver,
+            outputs,
+            asset: None,
+            operation: None,
+            htlc: None,
+            contract: None,
+            staking: None,
+            governance: None,
+            valid_after: None,
+            valid_until: None,
+            memo: None,
+            hash: String::new(),
+            signatures: vec![],
+        };
+        transaction.hash = transaction.calculate_hash();
+        transaction
+    }
+
+    /// what the transaction does, none if it sets several operations
+    pub fn kind(&self) -> Option<TransactionKind<'_>> {
+        let operations = (
+            &self.operation,
+            &self.htlc,
+            &self.contract,
+            &self.staking,
+            &self.governance,
+        );
+        match operations {
+            (None, None, None, None, None) => Some(TransactionKind::Transfer),
+            (Some(operation), None, None, None, None) => Some(TransactionKind::Asset(operation)),
+            (None, Some(operation), None, None, None) => Some(TransactionKind::Htlc(operation)),
+            (None, None, Some(operation), None, None) => Some(TransactionKind::Contract(operation)),
+            (None, None, None, Some(operation), None) => Some(TransactionKind::Staking(operation)),
+            (None, None, None, None, Some(operation)) => {
+                Some(TransactionKind::Governance(operation))
+            }
+            _ => None,
+        }
+    }
+
+    /// the receivers and the amounts they are credited, one for a plain transfer,
+    /// none for an operation
+    pub fn outputs(&self) -> Vec<(&str, u64)> {
+        if !matches!(self.kind(), Some(TransactionKind::Transfer)) {
+            vec![]
+        } else if self.outputs.is_empty() {
+            vec![(self.receiver.as_str(), self.amount)]
+        } else {
+            self.outputs
+                .iter()
+                .map(|output| (output.receiver.as_str(), output.amount))
+                .collect()
+        }
+    }
+
+    pub fn receivers(&self) -> Vec<String> {
+        self.outputs()
+            .iter()
+            .map(|(receiver, _)| receiver.to_string())
+            .collect()
+    }
+
+    /// the amount debited from the sender, none if it overflows
+    pub fn total(&self) -> Option<u64> {
+        self.outputs()
+            .iter()
+            .try_fold(0u64, |total, (_, amount)| total.checked_add(*amount))
     }
 
-    pub fn sign(&mut self, signing_key: &SigningKey) -> Result<(), Box<dyn std::error::Error>> {
-        if self.signature.is_some() {
-            return Err("Transaction already signed".into());
+    /// the most gas the transaction may use, zero if it is not a call
+    pub fn gas(&self) -> u64 {
+        match &self.contract {
+            Some(ContractOperation::Call { gas, .. }) => *gas,
+            _ => 0,
         }
-        let sig = Utils::sign_data(&self.hash, signing_key);
-        self.signature = Some((Utils::encode_signature(&sig), sig));
+    }
+
+    /// the addresses the transaction touches, each once, the sender first
+    /// then the receivers or the counterparties of the operation
+    pub fn addresses(&self) -> Vec<String> {
+        let mut addresses = vec![self.sender.clone()];
+        let counterparties = self
+            .kind()
+            .map_or_else(Vec::new, |kind| kind.executor().counterparties(self));
+        for address in counterparties {
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+        addresses
+    }
+
+    /// restrict the blocks the transaction is valid in, before signing as the window is signed
+    pub fn validity_set(
+        &mut self,
+        valid_after: Option<Lock>,
+        valid_until: Option<Lock>,
+    ) -> Result<(), LedgerError> {
+        if !self.signatures.is_empty() {
+            return Err(LedgerError::AlreadySigned);
+        }
+        self.valid_after = valid_after;
+        self.valid_until = valid_until;
+        self.hash = self.calculate_hash();
         Ok(())
     }
 
-    /// verify the transaction hash and signature
+    /// transfer or lock the asset with the id instead of the native coin, before signing
+    pub fn asset_set(&mut self, asset: Option<String>) -> Result<(), LedgerError> {
+        if !self.signatures.is_empty() {
+            return Err(LedgerError::AlreadySigned);
+        }
+        if !self.transfers_asset() {
+            return Err(LedgerError::InvalidAsset(
+                "the operation transfers nothing".into(),
+            ));
+        }
+        self.asset = asset;
+        self.hash = self.calculate_hash();
+        Ok(())
+    }
+
+    /// attach a memo, before signing as the memo is signed
+    pub fn memo_set(&mut self, memo: Option<Memo>) -> Result<(), LedgerError> {
+        if !self.signatures.is_empty() {
+            return Err(LedgerError::AlreadySigned);
+        }
+        if let Some(memo) = &memo {
+            if memo.len() > MAX_MEMO_SIZE {
+                return Err(LedgerError::MemoTooLarge {
+                    size: memo.len(),
+                    limit: MAX_MEMO_SIZE,
+                });
+            }
+        }
+        self.memo = memo;
+        self.hash = self.calculate_hash();
+        Ok(())
+    }
+
+    /// the transaction may be included in the block at `index` with `timestamp`
+    pub fn validity_check(&self, index: u64, timestamp: u64) -> Result<(), LedgerError> {
+        if let Some(lock) = self.valid_after {
+            if !lock.reached(index, timestamp) {
+                return Err(LedgerError::TransactionLocked {
+                    sender: self.sender.clone(),
+                    nonce: self.nonce,
+                    valid_after: lock.to_string(),
+                });
+            }
+        }
+        if let Some(lock) = self.valid_until {
+            if lock.passed(index, timestamp) {
+                return Err(LedgerError::TransactionExpired {
+                    sender: self.sender.clone(),
+                    nonce: self.nonce,
+                    valid_until: lock.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn authority(&self) -> &Authority {
+        &self.authority
+    }
+
+    /// the scheme of a single key sender, multisig keys may mix schemes
+    pub fn scheme(&self) -> Option<Scheme> {
+        match &self.authority {
+            Authority::Key(key) => Some(key.scheme()),
+            Authority::Multisig(_) => None,
+        }
+    }
+
+    /// add a hex signature made by the authority key at position `key`
+    pub fn signature_add(&mut self, key: usize, signature: &str) -> Result<(), LedgerError> {
+        let scheme = match self.authority.keys().get(key) {
+            Some(public_key) => public_key.scheme(),
+            None => {
+                return Err(LedgerError::InvalidKey(format!(
+                    "no key at position {} of the sender",
+                    key
+                )))
+            }
+        };
+        if self.signatures.iter().any(|s| s.key == key) {
+            return Err(LedgerError::AlreadySigned);
+        }
+        Signature::decode(scheme, signature)
+            .map_err(|e| LedgerError::InvalidSignature(e.to_string()))?;
+        self.signatures.push(KeySignature {
+            key,
+            encoded: signature.to_string(),
+        });
+        Ok(())
+    }
+
+    pub fn sign(&mut self, signing_key: &SigningKey) -> Result<(), LedgerError> {
+        self.sign_with(signing_key, SigningMode::Randomized)
+    }
+
+    pub fn sign_with(
+        &mut self,
+        signing_key: &SigningKey,
+        mode: SigningMode,
+    ) -> Result<(), LedgerError> {
+        self.sign_with_key(&PrivateKey::Ecdsa(signing_key.clone()), mode)
+    }
+
+    /// sign with one of the authority keys, of any scheme, once per key
+    pub fn sign_with_key(
+        &mut self,
+        private_key: &PrivateKey,
+        mode: SigningMode,
+    ) -> Result<(), LedgerError> {
+        let key = match &self.authority {
+            // a single key transaction is signed once, by whoever holds the key
+            Authority::Key(_) => 0,
+            Authority::Multisig(multisig) => multisig
+                .position(&private_key.public_key())
+                .ok_or_else(|| {
+                    LedgerError::InvalidKey(format!("not a key of multisig {}", self.sender))
+                })?,
+        };
+        let signature = private_key.sign(&self.hash, mode);
+        self.signature_add(key, &signature.encode())
+    }
+
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    /// the number of bytes the transaction occupies in a block
+    pub fn size(&self) -> usize {
+        8 + 8
+            + self.sender.len()
+            + self.authority.size()
+            + self.receiver.len()
+            + self
+                .outputs
+                .iter()
+                .map(|output| output.receiver.len() + 8)
+                .sum::<usize>()
+            + self.asset.as_ref().map_or(0, String::len)
+            + self
+                .operation
+                .as_ref()
+                .map_or(0, |operation| operation.to_string().len())
+            + self.htlc.as_ref().map_or(0, |htlc| htlc.to_string().len())
+            + self
+                .contract
+                .as_ref()
+                .map_or(0, |contract| contract.to_string().len())
+            + self
+                .staking
+                .as_ref()
+                .map_or(0, |staking| staking.to_string().len())
+            + self
+                .governance
+                .as_ref()
+                .map_or(0, |governance| governance.to_string().len())
+            + self.memo.as_ref().map_or(0, Memo::len)
+            + self.hash.len()
+            + self
+                .signatures
+                .iter()
+                .map(|s| 8 + s.encoded.len())
+                .sum::<usize>()
+    }
+
+    /// the hex DER signatures with their key positions, if signed
+    pub(crate) fn signatures_encoded(&self) -> Option<String> {
+        if self.signatures.is_empty() {
+            return None;
+        }
+        let signatures: Vec<String> = self
+            .signatures
+            .iter()
+            .map(|s| format!("{}:{}", s.key, s.encoded))
+            .collect();
+        Some(signatures.join(","))
+    }
+
+    /// verify the transaction hash, addresses and signatures
     pub fn verify(&self) -> bool {
-        self.verify_hash() && self.verify_signature()
+        self.verify_except_signature() && self.verify_signature()
     }
 
-    fn verify_hash(&self) -> bool {
-        self.hash
-            == Transaction::calculate_hash(self.nonce, &self.sender, &self.receiver, self.amount)
+    /// the cheap checks, for a transaction whose signatures are known to be valid
+    pub(crate) fn verify_except_signature(&self) -> bool {
+        self.verify_hash()
+            && self.kind_check().is_ok()
+            && self.verify_addresses()
+            && self.verify_memo()
+    }
+
+    /// the transaction is of one kind and valid for it, with an asset only if it moves one
+    fn kind_check(&self) -> Result<(), LedgerError> {
+        let kind = self.kind().ok_or_else(|| LedgerError::InvalidTransaction {
+            sender: self.sender.clone(),
+            nonce: self.nonce,
+        })?;
+        let executor = kind.executor();
+        if self.asset.is_some() && !executor.transfers_asset() {
+            return Err(LedgerError::InvalidAsset(
+                "the operation transfers nothing".into(),
+            ));
+        }
+        executor.verify(self)
     }
 
-    fn verify_signature(&self) -> bool {
-        match self.signature {
-            None => false,
-            Some(ref signature) => {
-                Utils::verify_signature(&self.hash, &signature.1, &self.sender_key)
+    fn transfers_asset(&self) -> bool {
+        self.kind()
+            .is_some_and(|kind| kind.executor().transfers_asset())
+    }
+
+    fn verify_memo(&self) -> bool {
+        self.memo
+            .as_ref()
+            .is_none_or(|memo| memo.len() <= MAX_MEMO_SIZE)
+    }
+
+    /// the authority hashes to the sender address and the receiver addresses are well formed
+    fn verify_addresses(&self) -> bool {
+        if let Authority::Multisig(multisig) = &self.authority {
+            if multisig.verify().is_err() {
+                return false;
             }
         }
+        self.sender == self.authority.address()
+            && self
+                .outputs()
+                .iter()
+                .all(|(receiver, _)| Utils::decode_address(receiver).is_ok())
+    }
+
+    /// a batch has a bounded number of outputs with a total that fits, and no single receiver
+    pub(crate) fn outputs_check(&self) -> Result<(), LedgerError> {
+        if self.outputs.is_empty() {
+            return Ok(());
+        }
+        if !self.receiver.is_empty() || self.amount != 0 {
+            return Err(LedgerError::InvalidOutputs(
+                "a batch has no single receiver".into(),
+            ));
+        }
+        if self.outputs.len() > MAX_OUTPUTS {
+            return Err(LedgerError::InvalidOutputs(format!(
+                "{} outputs, at most {}",
+                self.outputs.len(),
+                MAX_OUTPUTS
+            )));
+        }
+        if self.total().is_none() {
+            return Err(LedgerError::InvalidOutputs(
+                "the total amount overflows".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn verify_hash(&self) -> bool {
+        self.hash == self.calculate_hash()
     }
 
-    fn calculate_hash(nonce: u64, sender: &str, receiver: &str, amount: u64) -> String {
-        Utils::hash_data(&format!("{}{}{}{}", nonce, sender, receiver, amount))
+    /// at least threshold valid signatures by distinct authority keys, and no other signature
+    pub(crate) fn verify_signature(&self) -> bool {
+        let keys = self.authority.keys();
+        let mut signed = HashSet::new();
+        self.signatures.len() >= self.authority.threshold()
+            && self.signatures.iter().all(|s| {
+                let Some(key) = keys.get(s.key) else {
+                    return false;
+                };
+                signed.insert(s.key)
+                    && Signature::decode(key.scheme(), &s.encoded)
+                        .is_ok_and(|signature| key.verify(&self.hash, &signature))
+            })
     }
+
+    /// the hash of the canonical JSON of the fields the signatures cover, every field
+    /// delimited so no two transactions share a preimage
+    fn calculate_hash(&self) -> String {
+        let preimage = Preimage {
+            nonce: self.nonce,
+            sender: &self.sender,
+            receiver: &self.receiver,
+            amount: self.amount,
+            outputs: &self.outputs,
+            asset: &self.asset,
+            operation: &self.operation,
+            htlc: &self.htlc,
+            contract: &self.contract,
+            staking: &self.staking,
+            governance: &self.governance,
+            valid_after: &self.valid_after,
+            valid_until: &self.valid_until,
+            memo: &self.memo,
+        };
+        let data = serde_json::to_string(&preimage).expect("the fields serialize");
+        Utils::hash_data(&data)
+    }
+}
+
+/// what a transaction hash covers
+#[derive(Serialize)]
+struct Preimage<'a> {
+    nonce: u64,
+    sender: &'a str,
+    receiver: &'a str,
+    amount: u64,
+    outputs: &'a [Output],
+    asset: &'a Option<String>,
+    operation: &'a Option<AssetOperation>,
+    htlc: &'a Option<HtlcOperation>,
+    contract: &'a Option<ContractOperation>,
+    staking: &'a Option<StakingOperation>,
+    governance: &'a Option<GovernanceOperation>,
+    valid_after: &'a Option<Lock>,
+    valid_until: &'a Option<Lock>,
+    memo: &'a Option<Memo>,
+}
+
+fn address_check(address: &str) -> Result<(), LedgerError> {
+    Utils::decode_address(address).map_err(|e| LedgerError::InvalidAddress {
+        address: address.to_string(),
+        reason: e.to_string(),
+    })?;
+    Ok(())
 }
 
 // tests
@@ -83,9 +832,156 @@ mod tests {
         let sender_key = "MIGEAgEAMBAGByqGSM49AgEGBSuBBAAKBG0wawIBAQQgGYFjSRDEGRmqvaJreuMY22pZz3TojuOm2dEmxhtbPTyhRANCAARSnspJBeKF9TrV5WmDTsJXb/wtihZ4YyXRmGASMIbzdYuW+B5vh1B/dRvZ15Ne8ehUQ/xH023fVx1STJzkSeoS";
         let sender = "MFYwEAYHKoZIzj0CAQYFK4EEAAoDQgAEUp7KSQXihfU61eVpg07CV2/8LYoWeGMl0ZhgEjCG83WLlvgeb4dQf3Ub2deTXvHoVEP8R9Nt31cdUkyc5EnqEg==";
         let signing_key = Utils::get_signing_key(sender_key).unwrap();
+        let address = Utils::address(signing_key.verifying_key());
+
+        let mut transaction = Transaction::new(0, 100, sender, &address, None).unwrap();
+        assert_eq!(transaction.sender, address);
+        transaction.sign(&signing_key).unwrap();
+        assert!(transaction.verify());
+        assert!(matches!(
+            transaction.sign(&signing_key),
+            Err(LedgerError::AlreadySigned)
+        ));
+
+        // a typo in the receiver address fails the checksum
+        let mut typo = address.clone();
+        let last = typo.pop().unwrap();
+        typo.push(if last == 'q' { 'p' } else { 'q' });
+        assert!(matches!(
+            Transaction::new(0, 100, sender, &typo, None),
+            Err(LedgerError::InvalidAddress { .. })
+        ));
+    }
+
+    #[test]
+    fn test_fixture() {
+        let sender_key = "MIGEAgEAMBAGByqGSM49AgEGBSuBBAAKBG0wawIBAQQgGYFjSRDEGRmqvaJreuMY22pZz3TojuOm2dEmxhtbPTyhRANCAARSnspJBeKF9TrV5WmDTsJXb/wtihZ4YyXRmGASMIbzdYuW+B5vh1B/dRvZ15Ne8ehUQ/xH023fVx1STJzkSeoS";
+        let sender = "MFYwEAYHKoZIzj0CAQYFK4EEAAoDQgAEUp7KSQXihfU61eVpg07CV2/8LYoWeGMl0ZhgEjCG83WLlvgeb4dQf3Ub2deTXvHoVEP8R9Nt31cdUkyc5EnqEg==";
+        let receiver = "crt1az98ashxv3pa5x68yrz6ve8vz0ylaey28ccmy7";
+        let hash = "823280261c0db62ab2f443cec1595c911c407deb7f5c0b8d26c37ee6269890ae";
+        let signature = "30450221008d12f102d2a5ec40e3121fd842a4862f8cd688410969f2fbaf0b08ab866bd13a02206a7b2835f9b0abe59596e88a72af2ff7ff635babdac55a9221e63f1ec8d5444a";
+
+        let mut transaction = Transaction::new(1, 40, sender, receiver, None).unwrap();
+        transaction
+            .sign_with(
+                &Utils::get_signing_key(sender_key).unwrap(),
+                SigningMode::Deterministic,
+            )
+            .unwrap();
+        assert_eq!(transaction.hash(), hash);
+        assert_eq!(transaction.signatures[0].encoded, signature);
+
+        let transaction = Transaction::new(1, 40, sender, receiver, Some(signature)).unwrap();
+        assert!(transaction.verify());
+    }
+
+    #[test]
+    fn test_schemes() {
+        let receiver = "crt1az98ashxv3pa5x68yrz6ve8vz0ylaey28ccmy7";
+        for scheme in [Scheme::Ecdsa, Scheme::Schnorr, Scheme::Ed25519] {
+            let private_key = PrivateKey::generate(scheme);
+            let public_key = private_key.public_key();
+            let mut transaction =
+                Transaction::new(1, 40, &public_key.to_string(), receiver, None).unwrap();
+            assert_eq!(transaction.scheme(), Some(scheme));
+            assert_eq!(transaction.sender, public_key.address());
+            transaction
+                .sign_with_key(&private_key, SigningMode::Deterministic)
+                .unwrap();
+            assert!(transaction.verify());
+
+            let json = serde_json::to_string(&transaction).unwrap();
+            let transaction: Transaction = serde_json::from_str(&json).unwrap();
+            assert!(transaction.verify());
+        }
+
+        // signed by a key of another scheme
+        let schnorr = PrivateKey::generate(Scheme::Schnorr);
+        let mut transaction =
+            Transaction::new(1, 40, &schnorr.public_key().to_string(), receiver, None).unwrap();
+        let ed25519 = PrivateKey::generate(Scheme::Ed25519);
+        let signature = ed25519.sign(&transaction.hash, SigningMode::Deterministic);
+        transaction.signature_add(0, &signature.encode()).unwrap();
+        assert!(!transaction.verify());
+    }
+
+    #[test]
+    fn test_memo() {
+        let sender_key = "MIGEAgEAMBAGByqGSM49AgEGBSuBBAAKBG0wawIBAQQgGYFjSRDEGRmqvaJreuMY22pZz3TojuOm2dEmxhtbPTyhRANCAARSnspJBeKF9TrV5WmDTsJXb/wtihZ4YyXRmGASMIbzdYuW+B5vh1B/dRvZ15Ne8ehUQ/xH023fVx1STJzkSeoS";
+        let sender = "MFYwEAYHKoZIzj0CAQYFK4EEAAoDQgAEUp7KSQXihfU61eVpg07CV2/8LYoWeGMl0ZhgEjCG83WLlvgeb4dQf3Ub2deTXvHoVEP8R9Nt31cdUkyc5EnqEg==";
+        let receiver = "crt1az98ashxv3pa5x68yrz6ve8vz0ylaey28ccmy7";
+        let signing_key = Utils::get_signing_key(sender_key).unwrap();
+
+        let mut transaction = Transaction::new(1, 40, sender, receiver, None).unwrap();
+        let plain = transaction.hash().to_string();
+        let size = transaction.size();
+        assert!(matches!(
+            transaction.memo_set(Some(Memo::Binary(vec![0; MAX_MEMO_SIZE + 1]))),
+            Err(LedgerError::MemoTooLarge { .. })
+        ));
+        transaction
+            .memo_set(Some(Memo::Text("invoice 42".into())))
+            .unwrap();
+        assert_ne!(transaction.hash(), plain);
+        assert_eq!(transaction.size(), size + 10);
+        transaction.sign(&signing_key).unwrap();
+
+        let json = serde_json::to_string(&transaction).unwrap();
+        assert!(json.contains(r#""memo":{"text":"invoice 42"}"#));
+        let mut transaction: Transaction = serde_json::from_str(&json).unwrap();
+        assert!(transaction.verify());
+
+        // the memo is signed
+        transaction.memo = Some(Memo::Binary(b"invoice 42".to_vec()));
+        assert!(!transaction.verify());
+    }
+
+    #[test]
+    fn test_batch() {
+        let sender = "MFYwEAYHKoZIzj0CAQYFK4EEAAoDQgAEUp7KSQXihfU61eVpg07CV2/8LYoWeGMl0ZhgEjCG83WLlvgeb4dQf3Ub2deTXvHoVEP8R9Nt31cdUkyc5EnqEg==";
+        let receiver = "crt1az98ashxv3pa5x68yrz6ve8vz0ylaey28ccmy7";
+        let output = |amount| Output {
+            receiver: receiver.into(),
+            amount,
+        };
+
+        let transaction = Transaction::new_batch(1, sender, vec![output(40), output(2)]).unwrap();
+        assert_eq!(transaction.total(), Some(42));
+        assert_eq!(transaction.outputs(), vec![(receiver, 40), (receiver, 2)]);
+        assert_eq!(transaction.addresses().len(), 2);
+        assert!(transaction.verify_except_signature());
+
+        assert!(matches!(
+            Transaction::new_batch(1, sender, vec![output(u64::MAX), output(1)]),
+            Err(LedgerError::InvalidOutputs(_))
+        ));
+        assert!(matches!(
+            Transaction::new_batch(1, sender, vec![]),
+            Err(LedgerError::InvalidOutputs(_))
+        ));
+        assert!(matches!(
+            Transaction::new_batch(1, sender, vec![output(1); MAX_OUTPUTS + 1]),
+            Err(LedgerError::InvalidOutputs(_))
+        ));
+
+        // a batch with a single receiver as well is malformed
+        let mut transaction = transaction;
+        transaction.receiver = receiver.into();
+        transaction.hash = transaction.calculate_hash();
+        assert!(!transaction.verify_except_signature());
+    }
+
+    #[test]
+    fn test_hash_fields() {
+        let sender = "MFYwEAYHKoZIzj0CAQYFK4EEAAoDQgAEUp7KSQXihfU61eVpg07CV2/8LYoWeGMl0ZhgEjCG83WLlvgeb4dQf3Ub2deTXvHoVEP8R9Nt31cdUkyc5EnqEg==";
+        let receiver = "crt1az98ashxv3pa5x68yrz6ve8vz0ylaey28ccmy7";
 
-        let mut transaction = Transaction::new(0, 100, sender, sender, None).unwrap();
-        transaction.sign(&signing_key);
-        assert_eq!(transaction.verify(), true);
+        // one field running into the next was one preimage when they were concatenated
+        let mut first = Transaction::new(1, 40, sender, receiver, None).unwrap();
+        first.asset_set(Some("xmemo:text:y".into())).unwrap();
+        let mut second = Transaction::new(1, 40, sender, receiver, None).unwrap();
+        second.asset_set(Some("x".into())).unwrap();
+        second.memo_set(Some(Memo::Text("y".into()))).unwrap();
+        assert_ne!(first.hash(), second.hash());
     }
 }
@@ -0,0 +1,190 @@
+use crate::BLOCK_PENALTY;
+use ledger::error::LedgerError;
+use node::error::NodeError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum NetworkError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Malformed request: {0}")]
+    MalformedRequest(String),
+    #[error("Malformed response: {0}")]
+    MalformedResponse(String),
+    #[error("Unknown recipient")]
+    UnknownRecipient,
+    #[error("Unknown sender")]
+    UnknownSender,
+    #[error("Invalid signature")]
+    InvalidSignature,
+    #[error("Sender banned")]
+    Banned,
+    #[error("Node unavailable")]
+    NodeUnavailable,
+    #[error(transparent)]
+    Node(#[from] NodeError),
+}
+
+impl NetworkError {
+    /// the status code reported to the peer in the response
+    pub fn status(&self) -> usize {
+        match self {
+            NetworkError::Io(_) => 500,
+            NetworkError::MalformedRequest(_) => 400,
+            NetworkError::MalformedResponse(_) => 502,
+            NetworkError::UnknownRecipient => 404,
+            NetworkError::UnknownSender | NetworkError::Banned => 403,
+            NetworkError::InvalidSignature => 401,
+            NetworkError::NodeUnavailable => 503,
+            NetworkError::Node(error) => match error {
+                NodeError::InvalidMessage(_)
+                | NodeError::InvalidKey(_)
+                | NodeError::Keystore(_) => 400,
+                NodeError::InvalidBlock(error) | NodeError::Ledger(error) => ledger_status(error),
+            },
+        }
+    }
+
+    /// the penalty points the sender of the request earns
+    /// only blocks invalid on their own are penalized, a block an honest peer may relay
+    /// from another fork, with another clock or another view of the stake is not
+    pub fn penalty(&self) -> u32 {
+        match self {
+            NetworkError::Node(NodeError::InvalidBlock(
+                LedgerError::InvalidBlockHash { .. }
+                | LedgerError::MissingBlockSignature { .. }
+                | LedgerError::InvalidBlockSignature { .. }
+                | LedgerError::TooManyTransactions { .. }
+                | LedgerError::BlockTooLarge { .. }
+                | LedgerError::BlockGasExceeded { .. }
+                | LedgerError::DuplicateTransaction { .. }
+                | LedgerError::InvalidTransaction { .. }
+                | LedgerError::InvalidGenesis,
+            )) => BLOCK_PENALTY,
+            _ => 0,
+        }
+    }
+}
+
+fn ledger_status(error: &LedgerError) -> usize {
+    match error {
+        LedgerError::TransactionSeen { .. }
+        | LedgerError::TransactionKnown { .. }
+        | LedgerError::BlockKnown { .. } => 409,
+        LedgerError::InvalidTransaction { .. }
+        | LedgerError::InvalidKey(_)
+        | LedgerError::InvalidSignature(_)
+        | LedgerError::InvalidAddress { .. }
+        | LedgerError::InvalidMultisig(_)
+        | LedgerError::InvalidOutputs(_)
+        | LedgerError::InvalidAsset(_)
+        | LedgerError::InvalidHtlc(_)
+        | LedgerError::InvalidContract(_)
+        | LedgerError::InvalidStaking(_)
+        | LedgerError::AlreadySigned
+        | LedgerError::InvalidGenesis
+        | LedgerError::InvalidBlockHash { .. }
+        | LedgerError::MissingBlockSignature { .. }
+        | LedgerError::InvalidBlockSignature { .. }
+        | LedgerError::DuplicateTransaction { .. }
+        | LedgerError::InvalidBranch => 400,
+        LedgerError::TooManyTransactions { .. }
+        | LedgerError::BlockTooLarge { .. }
+        | LedgerError::BlockGasExceeded { .. }
+        | LedgerError::MemoTooLarge { .. } => 413,
+        LedgerError::InvalidNonce { .. }
+        | LedgerError::InsufficientBalance { .. }
+        | LedgerError::BalanceOverflow { .. }
+        | LedgerError::UnknownAsset(_)
+        | LedgerError::NotIssuer { .. }
+        | LedgerError::HtlcRejected { .. }
+        | LedgerError::UnknownContract(_)
+        | LedgerError::ContractFailed { .. }
+        | LedgerError::StakingRejected { .. }
+        | LedgerError::NotValidator { .. }
+        | LedgerError::NotGovernance { .. }
+        | LedgerError::AccountFrozen { .. }
+        | LedgerError::DailyLimitExceeded { .. }
+        | LedgerError::TransactionLocked { .. }
+        | LedgerError::TransactionExpired { .. }
+        | LedgerError::InvalidPrevHash { .. }
+        | LedgerError::TimestampNotMonotonic { .. }
+        | LedgerError::TimestampInFuture { .. }
+        | LedgerError::BranchNotLonger { .. } => 422,
+        LedgerError::NoGenesis => 503,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_block(error: LedgerError) -> NetworkError {
+        NetworkError::Node(NodeError::InvalidBlock(error))
+    }
+
+    #[test]
+    fn test_penalty_intrinsic() {
+        let errors = [
+            LedgerError::InvalidBlockHash { index: 1 },
+            LedgerError::MissingBlockSignature { index: 1 },
+            LedgerError::InvalidBlockSignature { index: 1 },
+            LedgerError::TooManyTransactions {
+                index: 1,
+                count: 2,
+                limit: 1,
+            },
+            LedgerError::BlockTooLarge {
+                index: 1,
+                size: 2,
+                limit: 1,
+            },
+            LedgerError::BlockGasExceeded {
+                index: 1,
+                gas: 2,
+                limit: 1,
+            },
+            LedgerError::DuplicateTransaction {
+                index: 1,
+                hash: "hash".into(),
+            },
+            LedgerError::InvalidTransaction {
+                sender: "sender".into(),
+                nonce: 1,
+            },
+            LedgerError::InvalidGenesis,
+        ];
+        for error in errors {
+            assert_eq!(invalid_block(error).penalty(), BLOCK_PENALTY);
+        }
+    }
+
+    #[test]
+    fn test_penalty_contextual() {
+        let errors = [
+            LedgerError::BlockKnown { index: 1 },
+            LedgerError::InvalidPrevHash {
+                expected: "a".into(),
+                got: "b".into(),
+            },
+            LedgerError::TimestampInFuture {
+                index: 1,
+                timestamp: 100,
+                now: 0,
+            },
+            LedgerError::TimestampNotMonotonic {
+                index: 1,
+                timestamp: 0,
+                prev_timestamp: 100,
+            },
+            LedgerError::NoGenesis,
+            LedgerError::NotValidator {
+                index: 1,
+                producer: "producer".into(),
+            },
+        ];
+        for error in errors {
+            assert_eq!(invalid_block(error).penalty(), 0);
+        }
+    }
+}
@@ -1,43 +1,69 @@
-use k256::ecdsa::VerifyingKey;
+pub mod error;
+
+use crate::error::NetworkError;
+use node::error::NodeError;
+use node::node::{Envelope, Node};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::io::Error as IoError;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::task;
 use utils::Utils;
 
+/// the penalty points after which a neighbor is banned
+pub const BAN_THRESHOLD: u32 = 100;
+/// the penalty points for relaying an invalid block
+pub const BLOCK_PENALTY: u32 = 10;
+
 pub struct Network {
     neighbors: HashMap<String, SocketAddr>,
+    /// penalty points of the neighbors, see `NetworkError::penalty`
+    penalties: Mutex<HashMap<String, u32>>,
     node: String,
-    rx: tokio::sync::mpsc::Receiver<String>,
-    node_tx: tokio::sync::mpsc::Sender<String>,
+    /// the public key of the node, sent with every request
+    node_key: String,
+    /// the node address signed by the node, sent with every request
+    node_signature: String,
+    node_tx: tokio::sync::mpsc::Sender<Envelope>,
 }
 
 impl Network {
     pub fn new(
-        node: String,
-        rx: tokio::sync::mpsc::Receiver<String>,
-        node_tx: tokio::sync::mpsc::Sender<String>,
-    ) -> Network {
-        Network {
-            node,
+        node: &Node,
+        node_tx: tokio::sync::mpsc::Sender<Envelope>,
+    ) -> Result<Network, NetworkError> {
+        let node_key = Utils::encode_verifying_key(node.verifying_key())
+            .map_err(|e| NodeError::InvalidKey(e.to_string()))?;
+        Ok(Network {
+            node: node.id.clone(),
+            node_key,
+            node_signature: Utils::encode_signature(&node.sign(&node.id)),
             neighbors: HashMap::new(),
-            rx,
+            penalties: Mutex::new(HashMap::new()),
             node_tx,
-        }
+        })
     }
 
-    pub async fn run(self) -> Result<(), Box<dyn std::error::Error>> {
+    /// accept requests from the node at `addr` and relay the node messages to it
+    pub fn neighbor_add(&mut self, address: String, addr: SocketAddr) {
+        self.neighbors.insert(address, addr);
+    }
+
+    /// serve the neighbors and relay the messages of the node on `rx` to them
+    pub async fn run(self, rx: tokio::sync::mpsc::Receiver<String>) -> Result<(), NetworkError> {
         let listener = TcpListener::bind("0.0.0.0:8080").await?;
+        self.serve(listener, rx).await
+    }
 
+    async fn serve(
+        self,
+        listener: TcpListener,
+        rx: tokio::sync::mpsc::Receiver<String>,
+    ) -> Result<(), NetworkError> {
         let network = Arc::new(self);
-        //
-        // TODO listen to rx
-        //
-        //
+        task::spawn(network.clone().relay(rx));
         loop {
             let (stream, addr) = listener.accept().await?;
             let connection = Connection {
@@ -46,7 +72,6 @@ impl Network {
                 network: network.clone(),
                 stream,
                 addr,
-                from_key: None,
                 request: None,
                 response: None,
             };
@@ -55,26 +80,82 @@ impl Network {
             });
         }
     }
+
+    /// send every message to the neighbors that are not banned
+    /// a neighbor that can not be reached misses the message
+    async fn relay(self: Arc<Self>, mut rx: tokio::sync::mpsc::Receiver<String>) {
+        while let Some(message) = rx.recv().await {
+            for (neighbor, addr) in &self.neighbors {
+                if self.is_banned(neighbor) {
+                    continue;
+                }
+                let request = Request {
+                    to: neighbor.clone(),
+                    from: self.node.clone(),
+                    from_key: self.node_key.clone(),
+                    signature: self.node_signature.clone(),
+                    message: message.clone(),
+                };
+                let addr = *addr;
+                task::spawn(async move {
+                    // TODO use logger
+                    match send(addr, &request).await {
+                        Ok(response) if response.status != 200 => {
+                            println!("Relay to {} refused: {}", addr, response.message)
+                        }
+                        Ok(_) => {}
+                        Err(e) => println!("Relay to {} failed: {}", addr, e),
+                    }
+                });
+            }
+        }
+    }
+
+    fn penalize(&self, neighbor: &str, points: u32) {
+        let mut penalties = self.penalties.lock().unwrap();
+        let penalty = penalties.entry(neighbor.to_string()).or_insert(0);
+        *penalty = penalty.saturating_add(points);
+    }
+
+    fn is_banned(&self, neighbor: &str) -> bool {
+        let penalties = self.penalties.lock().unwrap();
+        penalties.get(neighbor).is_some_and(|p| *p >= BAN_THRESHOLD)
+    }
 }
 
 struct Connection {
     node: String,
-    node_tx: tokio::sync::mpsc::Sender<String>,
+    node_tx: tokio::sync::mpsc::Sender<Envelope>,
     network: Arc<Network>,
     stream: TcpStream,
     addr: SocketAddr,
-    from_key: Option<VerifyingKey>,
     request: Option<Request>,
     response: Option<Response>,
 }
 
 impl Connection {
     async fn process(mut self) {
-        self.read_request().await.unwrap_or(());
-        self.handle_request().await.unwrap_or(());
+        let result = match self.read_request().await {
+            Ok(()) => self.handle_request().await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            if let Some(request) = &self.request {
+                if e.penalty() > 0 {
+                    self.network.penalize(&request.from, e.penalty());
+                }
+            }
+            self.response = Some(Response {
+                status: e.status(),
+                from: self.node.clone(),
+                signature: "".into(),
+                message: e.to_string(),
+            });
+        }
         self.write_response().await.unwrap_or(());
     }
-    async fn read_request(&mut self) -> Result<(), IoError> {
+
+    async fn read_request(&mut self) -> Result<(), NetworkError> {
         let mut headers: HashMap<String, String> = HashMap::new();
         let mut byte = [0; 1];
         let mut buf = Vec::new();
@@ -93,10 +174,12 @@ impl Connection {
             }
         }
 
-        for line in String::from_utf8(buf).unwrap().split("\r\n") {
-            let mut parts = line.split(": ");
-            let key = parts.next().unwrap();
-            let value = parts.next().unwrap();
+        let buf = String::from_utf8(buf)
+            .map_err(|_| NetworkError::MalformedRequest("headers are not utf-8".into()))?;
+        for line in buf.split("\r\n") {
+            let (key, value) = line
+                .split_once(": ")
+                .ok_or_else(|| NetworkError::MalformedRequest(format!("header `{}`", line)))?;
             headers.insert(key.into(), value.into());
         }
 
@@ -105,55 +188,57 @@ impl Connection {
 
         let content_length = headers
             .get("Content-Length")
-            .unwrap()
+            .ok_or_else(|| NetworkError::MalformedRequest("missing Content-Length".into()))?
             .parse::<usize>()
-            .unwrap();
+            .map_err(|_| NetworkError::MalformedRequest("invalid Content-Length".into()))?;
         let mut buf = vec![0; content_length];
         self.stream.read_exact(&mut buf).await?;
 
-        let request = serde_json::from_slice(&buf).unwrap();
+        let request = serde_json::from_slice(&buf)
+            .map_err(|e| NetworkError::MalformedRequest(e.to_string()))?;
 
         self.request = Some(request);
         Ok(())
     }
 
-    async fn handle_request(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let request = self.request.as_ref().unwrap();
+    async fn handle_request(&mut self) -> Result<(), NetworkError> {
+        let request = self
+            .request
+            .as_ref()
+            .ok_or_else(|| NetworkError::MalformedRequest("empty request".into()))?;
 
         // to
         if request.to != self.node {
-            self.response = Some(Response {
-                status: 500,
-                from: self.node.clone(),
-                signature: "".into(),
-                message: "Unknown recipient".into(),
-            });
-            return Ok(());
+            return Err(NetworkError::UnknownRecipient);
         }
         // from
         if !self.network.neighbors.contains_key(&request.from) {
-            self.response = Some(Response {
-                status: 500,
-                from: self.node.clone(),
-                signature: "".into(),
-                message: "Unknown sender".into(),
-            });
-            return Ok(());
+            return Err(NetworkError::UnknownSender);
+        }
+        if self.network.is_banned(&request.from) {
+            return Err(NetworkError::Banned);
         }
         // signature
-        let from_key = Utils::get_verifying_key(&request.from)?;
-        let from_signature = Utils::decode_signature(&request.signature)?;
+        let from_key =
+            Utils::get_verifying_key(&request.from_key).map_err(|_| NetworkError::UnknownSender)?;
+        if Utils::address(&from_key) != request.from {
+            return Err(NetworkError::UnknownSender);
+        }
+        let from_signature = Utils::decode_signature(&request.signature)
+            .map_err(|_| NetworkError::InvalidSignature)?;
         if !Utils::verify_signature(&request.from, &from_signature, &from_key) {
-            self.response = Some(Response {
-                status: 500,
-                from: self.node.clone(),
-                signature: "".into(),
-                message: "Invalid signature".into(),
-            });
-            return Ok(());
+            return Err(NetworkError::InvalidSignature);
         }
 
-        self.node_tx.send(request.message.clone()).await?;
+        let (reply, outcome) = tokio::sync::oneshot::channel();
+        self.node_tx
+            .send(Envelope {
+                message: request.message.clone(),
+                reply,
+            })
+            .await
+            .map_err(|_| NetworkError::NodeUnavailable)?;
+        outcome.await.map_err(|_| NetworkError::NodeUnavailable)??;
         self.response = Some(Response {
             status: 200,
             from: self.node.clone(),
@@ -164,33 +249,37 @@ impl Connection {
         Ok(())
     }
 
-    async fn write_response(&mut self) -> Result<(), IoError> {
-        match &self.response {
-            Some(response) => {
-                self.stream
-                    .write_all(format!("{}", serde_json::to_string(response)?).as_bytes())
-                    .await?;
-            }
-            None => {}
-        };
+    async fn write_response(&mut self) -> Result<(), NetworkError> {
+        if let Some(response) = &self.response {
+            let response = serde_json::to_string(response).map_err(std::io::Error::from)?;
+            self.stream.write_all(response.as_bytes()).await?;
+        }
         Ok(())
     }
 }
 
-#[derive(Deserialize, Serialize)]
-#[serde(tag = "type")]
-enum Message {
-    Request(Request),
-    Response(Response),
+/// write the request the way `Connection::read_request` reads it, then read the response
+async fn send(addr: SocketAddr, request: &Request) -> Result<Response, NetworkError> {
+    let body = serde_json::to_vec(request).map_err(std::io::Error::from)?;
+    let mut stream = TcpStream::connect(addr).await?;
+    stream
+        .write_all(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes())
+        .await?;
+    stream.write_all(&body).await?;
+    let mut buf = Vec::new();
+    stream.read_to_end(&mut buf).await?;
+    serde_json::from_slice(&buf).map_err(|e| NetworkError::MalformedResponse(e.to_string()))
 }
 
 #[derive(Deserialize, Serialize)]
 struct Request {
-    /// The recipient node public key
+    /// The recipient node address
     to: String,
-    /// The sender node public key
+    /// The sender node address
     from: String,
-    /// THe sender node public key signed by the sender node private key
+    /// The sender node public key, it must hash to the sender address
+    from_key: String,
+    /// THe sender node address signed by the sender node private key
     signature: String,
     /// The message to be opened by the node
     message: String,
@@ -200,10 +289,50 @@ struct Request {
 struct Response {
     /// The status code of the response
     status: usize,
-    /// The sender node public key
+    /// The sender node address
     from: String,
-    /// The sender node public key signed by the sender node private key
+    /// The sender node address signed by the sender node private key
     signature: String,
     /// The message to be opened by the recipient node
     message: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ledger::block::Genesis;
+    use ledger::chain::Chain;
+    use tokio::sync::mpsc;
+
+    fn network(key: &str) -> (Network, mpsc::Receiver<Envelope>) {
+        let (node_tx, node_rx) = mpsc::channel(1);
+        let id = Utils::address(Utils::get_signing_key(key).unwrap().verifying_key());
+        let node = Node::new(
+            key.to_string(),
+            Chain::new(Genesis::new(&id)),
+            mpsc::channel(1).1,
+            mpsc::channel(1).0,
+        )
+        .unwrap();
+        (Network::new(&node, node_tx).unwrap(), node_rx)
+    }
+
+    #[tokio::test]
+    async fn test_relay() {
+        let (mut sender, _) = network(&Utils::generate_keypair().unwrap().0);
+        let (mut receiver, mut node_rx) = network(&Utils::generate_keypair().unwrap().0);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        sender.neighbor_add(receiver.node.clone(), listener.local_addr().unwrap());
+        receiver.neighbor_add(sender.node.clone(), "127.0.0.1:1".parse().unwrap());
+
+        let (_receiver_tx, receiver_rx) = mpsc::channel(1);
+        task::spawn(receiver.serve(listener, receiver_rx));
+        let (sender_tx, sender_rx) = mpsc::channel(1);
+        task::spawn(Arc::new(sender).relay(sender_rx));
+
+        sender_tx.send("message".into()).await.unwrap();
+        let envelope = node_rx.recv().await.unwrap();
+        assert_eq!(envelope.message, "message");
+        envelope.reply.send(Ok(())).unwrap();
+    }
+}
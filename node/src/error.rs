@@ -0,0 +1,17 @@
+use ledger::error::LedgerError;
+use thiserror::Error;
+use utils::keystore::KeystoreError;
+
+#[derive(Debug, Error)]
+pub enum NodeError {
+    #[error("Invalid message: {0}")]
+    InvalidMessage(#[from] serde_json::Error),
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+    #[error(transparent)]
+    Keystore(#[from] KeystoreError),
+    #[error("Invalid block: {0}")]
+    InvalidBlock(LedgerError),
+    #[error(transparent)]
+    Ledger(#[from] LedgerError),
+}
@@ -1,54 +1,132 @@
-use k256::ecdsa::{SigningKey, VerifyingKey};
+use crate::error::NodeError;
+use k256::ecdsa::{Signature, SigningKey, VerifyingKey};
 use ledger::block::Block;
+use ledger::contract;
+use ledger::event::Event;
 use ledger::{chain::Chain, transaction::Transaction};
 use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::time::{Instant, MissedTickBehavior};
+use utils::keystore::Keystore;
+use utils::scheme::PrivateKey;
 use utils::Utils;
 
+// the validity of blocks with contract transactions must not depend on how a node is built
+const _: () = assert!(contract::RUNTIME, "a node runs contracts");
+
+/// how many events a slow subscriber may fall behind before missing some
+pub const EVENTS_CAPACITY: usize = 1024;
+/// how often in seconds the node checks whether it is its turn to produce a block
+pub const BLOCK_INTERVAL: u64 = 5;
+
+/// a message delivered to the node
+/// the outcome of handling it is sent back on `reply`
+pub struct Envelope {
+    pub message: String,
+    pub reply: tokio::sync::oneshot::Sender<Result<(), NodeError>>,
+}
+
 pub struct Node {
+    /// the node address, derived from its key
     pub id: String,
-    verifying_key: VerifyingKey,
     key: SigningKey,
-    chain: Chain,
-    rx: tokio::sync::mpsc::Receiver<String>,
+    chain: Arc<RwLock<Chain>>,
+    rx: tokio::sync::mpsc::Receiver<Envelope>,
     network_tx: tokio::sync::mpsc::Sender<String>,
+    events_tx: tokio::sync::broadcast::Sender<Event>,
 }
 
 impl Node {
     pub fn new(
-        id: String,
         key: String,
         chain: Chain,
-        rx: tokio::sync::mpsc::Receiver<String>,
+        rx: tokio::sync::mpsc::Receiver<Envelope>,
         network_tx: tokio::sync::mpsc::Sender<String>,
-    ) -> Result<Node, Box<dyn std::error::Error>> {
-        let signing_key = Utils::get_signing_key(&key)?;
-        let verifying_key = Utils::get_verifying_key(&id)?;
-
+    ) -> Result<Node, NodeError> {
+        let signing_key =
+            Utils::get_signing_key(&key).map_err(|e| NodeError::InvalidKey(e.to_string()))?;
         Ok(Node {
-            id,
-            verifying_key: verifying_key,
+            id: Utils::address(signing_key.verifying_key()),
             key: signing_key,
-            chain,
+            chain: Arc::new(RwLock::new(chain)),
             rx,
             network_tx,
+            events_tx: tokio::sync::broadcast::channel(EVENTS_CAPACITY).0,
         })
     }
 
-    pub async fn run(mut self) -> Result<(), Box<dyn std::error::Error>> {
+    /// a node with the key from the keystore, an ECDSA key as blocks are signed with ECDSA
+    pub fn with_keystore(
+        keystore: &Keystore,
+        password: &str,
+        chain: Chain,
+        rx: tokio::sync::mpsc::Receiver<Envelope>,
+        network_tx: tokio::sync::mpsc::Sender<String>,
+    ) -> Result<Node, NodeError> {
+        let PrivateKey::Ecdsa(key) = keystore.decrypt(password)? else {
+            return Err(NodeError::InvalidKey(format!(
+                "a {} key can not sign blocks",
+                keystore.scheme
+            )));
+        };
+        let key =
+            Utils::encode_signing_key(&key).map_err(|e| NodeError::InvalidKey(e.to_string()))?;
+        Node::new(key, chain, rx, network_tx)
+    }
+
+    /// the public key of the node, its address is derived from it
+    pub fn verifying_key(&self) -> &VerifyingKey {
+        self.key.verifying_key()
+    }
+
+    /// sign with the node key, how the node proves its address to its neighbors
+    pub fn sign(&self, data: &str) -> Signature {
+        Utils::sign_data(data, &self.key)
+    }
+
+    /// a handle to the chain for readers outside the node
+    /// all writes go through the node
+    pub fn chain(&self) -> Arc<RwLock<Chain>> {
+        self.chain.clone()
+    }
+
+    /// the chain events published by the node, `subscribe` to receive them
+    pub fn events(&self) -> tokio::sync::broadcast::Sender<Event> {
+        self.events_tx.clone()
+    }
+
+    /// handle the messages and produce a block whenever it is this node's turn
+    pub async fn run(mut self) -> Result<(), NodeError> {
+        let period = Duration::from_secs(BLOCK_INTERVAL);
+        let mut interval = tokio::time::interval_at(Instant::now() + period, period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
         loop {
-            if let Some(message) = self.rx.recv().await {
-                self.handle_message(message)?;
+            tokio::select! {
+                envelope = self.rx.recv() => {
+                    let Some(envelope) = envelope else {
+                        return Ok(());
+                    };
+                    let result = self.handle_message(envelope.message);
+                    self.publish_events();
+                    // the sender may have given up waiting, nothing to report to then
+                    let _ = envelope.reply.send(result);
+                }
+                _ = interval.tick() => {
+                    let due = self.chain.read().unwrap().producer_due();
+                    if due.as_deref() == Some(self.id.as_str()) {
+                        // a failed block leaves the chain as it was, the next turn tries again
+                        let _ = self.block_mint();
+                    }
+                }
             }
         }
     }
 
-    fn handle_message(&mut self, message: String) -> Result<(), Box<dyn std::error::Error>> {
-        match serde_json::from_str::<Message>(&message) {
-            Ok(message) => match message {
-                Message::Transaction(transaction) => self.handle_transaction(*transaction),
-                Message::Block(block) => self.handle_block(*block),
-            },
-            Err(_) => Err("Invalid message".into()),
+    fn handle_message(&mut self, message: String) -> Result<(), NodeError> {
+        match serde_json::from_str::<Message>(&message)? {
+            Message::Transaction(transaction) => self.handle_transaction(*transaction),
+            Message::Block(block) => self.handle_block(*block),
         }
     }
 
@@ -57,58 +135,56 @@ impl Node {
     /// nonce should be unique and increasing by 1 for each transaction from the same sender
     /// transactions will be processed in sequence of the nonce
     /// if the transaction is valid and not seen, it will be added to the mempool
-    fn handle_transaction(
-        &mut self,
-        transaction: Transaction,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        self.chain.transaction_add(transaction);
+    fn handle_transaction(&mut self, transaction: Transaction) -> Result<(), NodeError> {
+        self.chain.write().unwrap().transaction_add(transaction)?;
 
         Ok(())
     }
 
     /// blocks comes from other nodes
-    /// it should be signed by the sender
+    /// it should be signed by the producer
     /// it should be valid
     /// it should be the next block in the chain otherwise it will be added to the orphan blocks
     /// if the block is valid and not seen, it will be added to the chain
-    pub fn handle_block(&mut self, block: Block) -> Result<(), Box<dyn std::error::Error>> {
-        self.chain.block_add(block);
-        // // signature
-        // {
-        //     if !block.verify() {
-        //         return Err("Invalid signature".into());
-        //     }
-        // }
-        // // seen
-        // {
-        //     let top_block = self.chain.block_get_top_index();
-        //     if block.index <= top_block {
-        //         return Err("Block already seen".into());
-        //     } else if block.index > top_block + 1 {
-        //         // orphan blocks
-        //         return Ok(());
-        //     }
-        // }
-        //
-        // // verify block
-        // {
-        //     if !block.verify() {
-        //         return Err("Invalid block".into());
-        //     }
-        // }
-        //
-        // // add to chain
-        // {
-        //     self.chain.block_add(block);
-        // }
+    /// any failure is reported as `NodeError::InvalidBlock` so the sender can be penalized
+    pub fn handle_block(&mut self, block: Block) -> Result<(), NodeError> {
+        self.chain
+            .write()
+            .unwrap()
+            .block_add(block)
+            .map_err(NodeError::InvalidBlock)?;
 
         Ok(())
     }
+
+    /// produce a block from the mempool signed by this node and hand it to the network
+    pub fn block_mint(&mut self) -> Result<(), NodeError> {
+        let result = self.chain.write().unwrap().block_mint(&self.id, &self.key);
+        self.publish_events();
+        result?;
+
+        let block = self.chain.read().unwrap().block_last().cloned();
+        if let Some(block) = block {
+            let message = serde_json::to_string(&Message::Block(Box::new(block)))?;
+            // a full network queue drops the announcement rather than stall the node
+            let _ = self.network_tx.try_send(message);
+        }
+        Ok(())
+    }
+
+    fn publish_events(&self) {
+        let events = self.chain.write().unwrap().events_drain();
+        for event in events {
+            // there may be no subscribers
+            let _ = self.events_tx.send(event);
+        }
+    }
 }
 
+/// the messages the node accepts, serialized as JSON
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "type")]
-enum Message {
+pub enum Message {
     Transaction(Box<Transaction>),
     Block(Box<Block>),
 }
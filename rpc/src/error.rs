@@ -0,0 +1,33 @@
+use jsonrpsee::core::RegisterMethodError;
+use jsonrpsee::types::error::INTERNAL_ERROR_CODE;
+use jsonrpsee::types::ErrorObjectOwned;
+use node::error::NodeError;
+use thiserror::Error;
+
+/// the node did not answer
+pub const NODE_UNAVAILABLE_CODE: i32 = -32000;
+/// the node rejected the request, the message tells why
+pub const REJECTED_CODE: i32 = -32001;
+
+#[derive(Debug, Error)]
+pub enum RpcError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Register(#[from] RegisterMethodError),
+    #[error("Node unavailable")]
+    NodeUnavailable,
+    #[error(transparent)]
+    Node(#[from] NodeError),
+}
+
+impl From<RpcError> for ErrorObjectOwned {
+    fn from(error: RpcError) -> Self {
+        let code = match error {
+            RpcError::Node(_) => REJECTED_CODE,
+            RpcError::NodeUnavailable => NODE_UNAVAILABLE_CODE,
+            RpcError::Io(_) | RpcError::Register(_) => INTERNAL_ERROR_CODE,
+        };
+        ErrorObjectOwned::owned(code, error.to_string(), None::<()>)
+    }
+}
@@ -0,0 +1,330 @@
+pub mod error;
+
+use crate::error::RpcError;
+use jsonrpsee::server::{Server, SubscriptionMessage};
+use jsonrpsee::types::error::INVALID_PARAMS_CODE;
+use jsonrpsee::types::ErrorObjectOwned;
+use jsonrpsee::RpcModule;
+use ledger::block::Block;
+use ledger::chain::Chain;
+use ledger::event::Event;
+use ledger::index::TransactionLocation;
+use ledger::staking;
+use ledger::transaction::Transaction;
+use node::node::{Envelope, Message};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+
+/// JSON-RPC server for clients and wallets, over HTTP and WebSocket on the same port
+/// queries are answered from the chain, transactions are submitted to the node
+/// events are pushed to WebSocket clients that `subscribe`
+pub struct Rpc {
+    chain: Arc<RwLock<Chain>>,
+    node_tx: tokio::sync::mpsc::Sender<Envelope>,
+    events: tokio::sync::broadcast::Sender<Event>,
+}
+
+impl Rpc {
+    pub fn new(
+        chain: Arc<RwLock<Chain>>,
+        node_tx: tokio::sync::mpsc::Sender<Envelope>,
+        events: tokio::sync::broadcast::Sender<Event>,
+    ) -> Rpc {
+        Rpc {
+            chain,
+            node_tx,
+            events,
+        }
+    }
+
+    pub async fn run(self, addr: SocketAddr) -> Result<(), RpcError> {
+        let server = Server::builder().build(addr).await?;
+        let handle = server.start(self.module()?);
+        handle.stopped().await;
+        Ok(())
+    }
+
+    fn module(self) -> Result<RpcModule<Rpc>, RpcError> {
+        let mut module = RpcModule::new(self);
+
+        module.register_async_method("submitTransaction", |params, rpc, _| async move {
+            let transaction: Transaction = params.one()?;
+            let hash = transaction.hash().to_string();
+            rpc.submit(Message::Transaction(Box::new(transaction)))
+                .await?;
+            Ok::<_, ErrorObjectOwned>(hash)
+        })?;
+        module.register_method("getBlock", |params, rpc, _| {
+            let index: u64 = params.one()?;
+            let chain = rpc.chain.read().unwrap();
+            Ok::<_, ErrorObjectOwned>(chain.block_get(index).map(to_value))
+        })?;
+        // the native balance, or the balance in the asset given as the second parameter
+        module.register_method("getBalance", |params, rpc, _| {
+            let mut params = params.sequence();
+            let address: String = params.next()?;
+            let asset: Option<String> = params.optional_next()?;
+            let chain = rpc.chain.read().unwrap();
+            let balance = match asset {
+                Some(asset) => chain.asset_balance_get(&address, &asset),
+                None => chain.balance_get(&address),
+            };
+            Ok::<_, ErrorObjectOwned>(balance)
+        })?;
+        module.register_method("getAsset", |params, rpc, _| {
+            let id: String = params.one()?;
+            let chain = rpc.chain.read().unwrap();
+            Ok::<_, ErrorObjectOwned>(chain.asset_get(&id).cloned())
+        })?;
+        module.register_method("getHtlc", |params, rpc, _| {
+            let id: String = params.one()?;
+            let chain = rpc.chain.read().unwrap();
+            Ok::<_, ErrorObjectOwned>(chain.htlc_get(&id).cloned())
+        })?;
+        module.register_method("getContract", |params, rpc, _| {
+            let address: String = params.one()?;
+            let chain = rpc.chain.read().unwrap();
+            Ok::<_, ErrorObjectOwned>(chain.contract_get(&address).cloned())
+        })?;
+        // the hex value of the hex key in the storage of the contract
+        module.register_method("getStorage", |params, rpc, _| {
+            let (contract, key): (String, String) = params.parse()?;
+            let key = hex::decode(&key).map_err(|e| {
+                ErrorObjectOwned::owned(INVALID_PARAMS_CODE, e.to_string(), None::<()>)
+            })?;
+            let chain = rpc.chain.read().unwrap();
+            Ok::<_, ErrorObjectOwned>(chain.storage_get(&contract, &key).map(hex::encode))
+        })?;
+        module.register_method("getReceipt", |params, rpc, _| {
+            let hash: String = params.one()?;
+            let chain = rpc.chain.read().unwrap();
+            Ok::<_, ErrorObjectOwned>(chain.receipt_get(&hash).cloned())
+        })?;
+        module.register_method("getStake", |params, rpc, _| {
+            let address: String = params.one()?;
+            Ok::<_, ErrorObjectOwned>(rpc.chain.read().unwrap().stake_get(&address))
+        })?;
+        module.register_method("getUnbonding", |params, rpc, _| {
+            let id: String = params.one()?;
+            let chain = rpc.chain.read().unwrap();
+            Ok::<_, ErrorObjectOwned>(chain.unbonding_get(&id).cloned())
+        })?;
+        module.register_method("getGovernance", |_, rpc, _| {
+            let chain = rpc.chain.read().unwrap();
+            Ok::<_, ErrorObjectOwned>(chain.governance_get().map(str::to_string))
+        })?;
+        module.register_method("getControl", |params, rpc, _| {
+            let address: String = params.one()?;
+            Ok::<_, ErrorObjectOwned>(rpc.chain.read().unwrap().control_get(&address))
+        })?;
+        // the validators of the epoch given as parameter, or of the epoch of the last block
+        module.register_method("getValidators", |params, rpc, _| {
+            let epoch: Option<u64> = params.sequence().optional_next()?;
+            let chain = rpc.chain.read().unwrap();
+            let last = chain.block_last().map_or(0, |block| block.index);
+            let epoch = epoch.unwrap_or(staking::epoch(last));
+            Ok::<_, ErrorObjectOwned>(chain.validators_get(epoch).cloned())
+        })?;
+        module.register_method("getNonce", |params, rpc, _| {
+            let address: String = params.one()?;
+            Ok::<_, ErrorObjectOwned>(rpc.chain.read().unwrap().nonce_get(&address))
+        })?;
+        module.register_method("getTransaction", |params, rpc, _| {
+            let hash: String = params.one()?;
+            let chain = rpc.chain.read().unwrap();
+            let transaction =
+                chain
+                    .transaction_get(&hash)
+                    .map(|(location, transaction)| TransactionStatus {
+                        location,
+                        transaction: to_value(transaction),
+                    });
+            Ok::<_, ErrorObjectOwned>(transaction)
+        })?;
+        module.register_method("getChainHead", |_, rpc, _| {
+            let chain = rpc.chain.read().unwrap();
+            Ok::<_, ErrorObjectOwned>(chain.block_last().map(ChainHead::from))
+        })?;
+        module.register_method("getMempoolStatus", |_, rpc, _| {
+            let size = rpc.chain.read().unwrap().mempool_size();
+            Ok::<_, ErrorObjectOwned>(MempoolStatus { size })
+        })?;
+        module.register_subscription(
+            "subscribe",
+            "event",
+            "unsubscribe",
+            |params, pending, rpc, _| async move {
+                let filter: EventFilter = params.sequence().optional_next()?.unwrap_or_default();
+                let mut events = rpc.events.subscribe();
+                let sink = pending.accept().await?;
+                loop {
+                    let event = tokio::select! {
+                        _ = sink.closed() => break,
+                        event = events.recv() => event,
+                    };
+                    let event = match event {
+                        Ok(event) => event,
+                        // the subscriber missed some events, carry on with the latest
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    };
+                    if !filter.matches(&event) {
+                        continue;
+                    }
+                    if sink
+                        .send(SubscriptionMessage::from_json(&event)?)
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                Ok(())
+            },
+        )?;
+
+        Ok(module)
+    }
+
+    /// hand a message to the node and wait for the outcome
+    async fn submit(&self, message: Message) -> Result<(), RpcError> {
+        let message = serde_json::to_string(&message).map_err(std::io::Error::from)?;
+        let (reply, outcome) = tokio::sync::oneshot::channel();
+        self.node_tx
+            .send(Envelope { message, reply })
+            .await
+            .map_err(|_| RpcError::NodeUnavailable)?;
+        outcome.await.map_err(|_| RpcError::NodeUnavailable)??;
+        Ok(())
+    }
+}
+
+fn to_value<T: Serialize>(value: &T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or_default()
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TransactionStatus {
+    #[serde(flatten)]
+    pub location: TransactionLocation,
+    pub transaction: serde_json::Value,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ChainHead {
+    pub index: u64,
+    pub hash: String,
+    pub timestamp: u64,
+}
+
+impl From<&Block> for ChainHead {
+    fn from(block: &Block) -> Self {
+        ChainHead {
+            index: block.index,
+            hash: block.hash.clone(),
+            timestamp: block.timestamp,
+        }
+    }
+}
+
+/// selects the events a subscription receives, everything by default
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct EventFilter {
+    /// the event types, as in the `type` tag, e.g. `BlockApplied`
+    pub kinds: Option<Vec<String>>,
+    /// only events about this account
+    pub address: Option<String>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &Event) -> bool {
+        if let Some(kinds) = &self.kinds {
+            if !kinds.iter().any(|kind| kind == event.kind()) {
+                return false;
+            }
+        }
+        match &self.address {
+            Some(address) => event.concerns(address),
+            None => true,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MempoolStatus {
+    /// the number of transactions waiting to be included in a block
+    pub size: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ledger::block::Genesis;
+    use node::node::Node;
+    use utils::Utils;
+
+    const KEY_PRIV: &str = "MIGEAgEAMBAGByqGSM49AgEGBSuBBAAKBG0wawIBAQQgGYFjSRDEGRmqvaJreuMY22pZz3TojuOm2dEmxhtbPTyhRANCAARSnspJBeKF9TrV5WmDTsJXb/wtihZ4YyXRmGASMIbzdYuW+B5vh1B/dRvZ15Ne8ehUQ/xH023fVx1STJzkSeoS";
+    const KEY_PUB: &str = "MFYwEAYHKoZIzj0CAQYFK4EEAAoDQgAEUp7KSQXihfU61eVpg07CV2/8LYoWeGMl0ZhgEjCG83WLlvgeb4dQf3Ub2deTXvHoVEP8R9Nt31cdUkyc5EnqEg==";
+    const ADDRESS: &str = "crt1rda9nkk7dv63muvhry040whcfaed5ccygdyzfr";
+    const RECEIVER: &str = "crt1az98ashxv3pa5x68yrz6ve8vz0ylaey28ccmy7";
+
+    #[tokio::test]
+    async fn test() {
+        let (network_tx, _network_rx) = tokio::sync::mpsc::channel(1);
+        let (node_tx, node_rx) = tokio::sync::mpsc::channel(1);
+        // another validator, the node never produces and the head stays put
+        let genesis = Genesis::new(RECEIVER);
+        let mut chain = Chain::new(genesis.clone());
+        chain.block_add(genesis.block()).unwrap();
+        let node = Node::new(KEY_PRIV.into(), chain, node_rx, network_tx).unwrap();
+        let module = Rpc::new(node.chain(), node_tx, node.events())
+            .module()
+            .unwrap();
+        tokio::spawn(node.run());
+
+        let head: ChainHead = module.call("getChainHead", [(); 0]).await.unwrap();
+        assert_eq!(head.index, 0);
+        assert_eq!(head.hash, genesis.block().hash);
+        let balance: u64 = module.call("getBalance", [ADDRESS]).await.unwrap();
+        assert_eq!(balance, 0);
+        let balance: u64 = module
+            .call("getBalance", [ADDRESS, "unknown"])
+            .await
+            .unwrap();
+        assert_eq!(balance, 0);
+
+        let filter = EventFilter {
+            kinds: Some(vec!["TransactionAdded".into()]),
+            address: Some(ADDRESS.into()),
+        };
+        let mut subscription = module
+            .subscribe_unbounded("subscribe", [&filter])
+            .await
+            .unwrap();
+
+        let mut transaction = Transaction::new(1, 10, KEY_PUB, ADDRESS, None).unwrap();
+        transaction
+            .sign(&Utils::get_signing_key(KEY_PRIV).unwrap())
+            .unwrap();
+        let hash: String = module
+            .call("submitTransaction", [&transaction])
+            .await
+            .unwrap();
+        assert_eq!(hash, transaction.hash());
+        let (event, _) = subscription.next::<Event>().await.unwrap().unwrap();
+        assert_eq!(
+            event,
+            Event::TransactionAdded {
+                hash: hash.clone(),
+                sender: ADDRESS.into(),
+                receivers: vec![ADDRESS.into()],
+            }
+        );
+        let status: MempoolStatus = module.call("getMempoolStatus", [(); 0]).await.unwrap();
+        assert_eq!(status.size, 1);
+        let found: Option<TransactionStatus> =
+            module.call("getTransaction", [&hash]).await.unwrap();
+        assert!(found.is_none());
+    }
+}
@@ -0,0 +1,28 @@
+use thiserror::Error;
+
+/// decoding keys, signatures and addresses
+#[derive(Debug, Error)]
+pub enum CryptoError {
+    #[error("Invalid base64: {0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("Invalid hex: {0}")]
+    Hex(#[from] hex::FromHexError),
+    #[error("Invalid PEM: {0}")]
+    Pem(#[from] pem::PemError),
+    #[error("Invalid private key: {0}")]
+    InvalidPrivateKey(String),
+    #[error("Invalid public key: {0}")]
+    InvalidPublicKey(String),
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
+    #[error("Non-canonical signature encoding")]
+    NonCanonicalSignature,
+    #[error("High-S signature")]
+    HighS,
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+    #[error("Unknown signature scheme {0}")]
+    UnknownScheme(String),
+    #[error("Key encoding failed: {0}")]
+    Encoding(String),
+}
@@ -0,0 +1,114 @@
+use crate::Utils;
+use bip32::{DerivationPath, XPrv};
+use bip39::Mnemonic;
+use rand_core::OsRng;
+use thiserror::Error;
+
+/// the SLIP-44 coin type of the chain, "crt" in ASCII, so a mnemonic shared with another
+/// chain's wallet derives other keys
+pub const COIN_TYPE: u32 = 0x63_72_74;
+/// the BIP-44 path of the first account, `account_path(0)`
+pub const DEFAULT_PATH: &str = "m/44'/6517364'/0'/0/0";
+
+#[derive(Debug, Error)]
+pub enum HdError {
+    #[error("Invalid mnemonic: {0}")]
+    InvalidMnemonic(String),
+    #[error("Invalid derivation path: {0}")]
+    InvalidPath(String),
+    #[error("Derivation failed: {0}")]
+    Derivation(String),
+}
+
+/// a new random 24 word english BIP-39 mnemonic
+pub fn generate_mnemonic() -> String {
+    Mnemonic::generate_in_with(&mut OsRng, bip39::Language::English, 24)
+        .map(|mnemonic| mnemonic.to_string())
+        .expect("24 is a valid word count")
+}
+
+/// the BIP-44 path of the account at `index`
+pub fn account_path(index: u32) -> String {
+    format!("m/44'/{}'/0'/0/{}", COIN_TYPE, index)
+}
+
+/// derive the key pair at `path` from the mnemonic and the optional passphrase
+/// returns the base64 PKCS#8 private key and base64 SPKI public key,
+/// as `Utils::get_signing_key` and `Utils::get_verifying_key` expect them
+pub fn derive_keypair(
+    mnemonic: &str,
+    passphrase: &str,
+    path: &str,
+) -> Result<(String, String), HdError> {
+    let xprv = derive_xprv(mnemonic, passphrase, path)?;
+    let signing_key = xprv.private_key();
+    let private =
+        Utils::encode_signing_key(signing_key).map_err(|e| HdError::Derivation(e.to_string()))?;
+    let public = Utils::encode_verifying_key(signing_key.verifying_key())
+        .map_err(|e| HdError::Derivation(e.to_string()))?;
+    Ok((private, public))
+}
+
+/// the BIP-39 seed of the mnemonic and the optional passphrase
+fn seed(mnemonic: &str, passphrase: &str) -> Result<[u8; 64], HdError> {
+    let mnemonic = Mnemonic::parse_in(bip39::Language::English, mnemonic.trim())
+        .map_err(|e| HdError::InvalidMnemonic(e.to_string()))?;
+    Ok(mnemonic.to_seed(passphrase))
+}
+
+/// the extended private key at `path` of the seed of the mnemonic
+fn derive_xprv(mnemonic: &str, passphrase: &str, path: &str) -> Result<XPrv, HdError> {
+    let seed = seed(mnemonic, passphrase)?;
+    let path: DerivationPath = path
+        .parse()
+        .map_err(|e: bip32::Error| HdError::InvalidPath(e.to_string()))?;
+    XPrv::derive_from_path(seed, &path).map_err(|e| HdError::Derivation(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test() {
+        // the first BIP-39 test vector, https://github.com/trezor/python-mnemonic/blob/master/vectors.json
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        assert_eq!(
+            hex::encode(seed(mnemonic, "TREZOR").unwrap()),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+        let xprv = derive_xprv(mnemonic, "TREZOR", "m").unwrap();
+        assert_eq!(
+            xprv.to_string(bip32::Prefix::XPRV).as_str(),
+            "xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF"
+        );
+        assert_eq!(account_path(0), DEFAULT_PATH);
+        let (private, public) = derive_keypair(mnemonic, "TREZOR", DEFAULT_PATH).unwrap();
+        let signing_key = Utils::get_signing_key(&private).unwrap();
+        let verifying_key = Utils::get_verifying_key(&public).unwrap();
+        assert_eq!(signing_key.verifying_key(), &verifying_key);
+
+        assert_eq!(
+            derive_keypair(mnemonic, "TREZOR", DEFAULT_PATH).unwrap(),
+            (private.clone(), public)
+        );
+        assert_ne!(
+            derive_keypair(mnemonic, "TREZOR", &account_path(1))
+                .unwrap()
+                .0,
+            private
+        );
+        assert!(matches!(
+            derive_keypair("abandon abandon", "", DEFAULT_PATH),
+            Err(HdError::InvalidMnemonic(_))
+        ));
+        assert!(matches!(
+            derive_keypair(mnemonic, "", "m/x"),
+            Err(HdError::InvalidPath(_))
+        ));
+
+        let mnemonic = generate_mnemonic();
+        assert_eq!(mnemonic.split(' ').count(), 24);
+        assert!(derive_keypair(&mnemonic, "", DEFAULT_PATH).is_ok());
+    }
+}
@@ -0,0 +1,280 @@
+use crate::scheme::{PrivateKey, Scheme};
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use thiserror::Error;
+
+/// the address is authenticated with the key and the key is raw bytes of any scheme since version 2
+pub const VERSION: u32 = 2;
+pub const CIPHER: &str = "aes-256-gcm";
+pub const KDF: &str = "scrypt";
+
+#[derive(Debug, Error)]
+pub enum KeystoreError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("Unsupported keystore version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Unsupported cipher {0}")]
+    UnsupportedCipher(String),
+    #[error("Unsupported kdf {0}")]
+    UnsupportedKdf(String),
+    #[error("Invalid kdf params")]
+    InvalidKdfParams,
+    #[error("Invalid keystore: {0}")]
+    Invalid(String),
+    #[error("Wrong password")]
+    WrongPassword,
+}
+
+/// a private key encrypted with a password, stored as JSON
+/// modelled after the Ethereum v3 keystore, with an AEAD cipher in place of CTR + MAC
+#[derive(Serialize, Deserialize)]
+pub struct Keystore {
+    pub version: u32,
+    /// The address of the encrypted private key, see `PublicKey::address`
+    pub address: String,
+    /// The signature scheme of the encrypted private key
+    pub scheme: Scheme,
+    pub crypto: Crypto,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Crypto {
+    pub cipher: String,
+    /// The encrypted 32 byte private key, hex
+    pub ciphertext: String,
+    /// The cipher nonce, hex
+    pub nonce: String,
+    pub kdf: String,
+    pub kdfparams: KdfParams,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// log2 of the scrypt cost parameter N
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+    /// The salt, hex
+    pub salt: String,
+}
+
+impl KdfParams {
+    /// the recommended scrypt cost, with a random salt
+    pub fn new() -> KdfParams {
+        KdfParams::with_cost(15, 8, 1)
+    }
+
+    pub fn with_cost(log_n: u8, r: u32, p: u32) -> KdfParams {
+        let mut salt = [0u8; 32];
+        OsRng.fill_bytes(&mut salt);
+        KdfParams {
+            log_n,
+            r,
+            p,
+            salt: hex::encode(salt),
+        }
+    }
+
+    fn derive_key(&self, password: &str) -> Result<[u8; 32], KeystoreError> {
+        let salt = hex::decode(&self.salt).map_err(|_| KeystoreError::InvalidKdfParams)?;
+        let params = scrypt::Params::new(self.log_n, self.r, self.p, 32)
+            .map_err(|_| KeystoreError::InvalidKdfParams)?;
+        let mut key = [0u8; 32];
+        scrypt::scrypt(password.as_bytes(), &salt, &params, &mut key)
+            .map_err(|_| KeystoreError::InvalidKdfParams)?;
+        Ok(key)
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams::new()
+    }
+}
+
+impl Keystore {
+    /// encrypt a private key of any scheme, under its address
+    pub fn encrypt(
+        private_key: &PrivateKey,
+        password: &str,
+        kdfparams: KdfParams,
+    ) -> Result<Keystore, KeystoreError> {
+        let address = private_key.public_key().address();
+        let key = kdfparams.derive_key(password)?;
+        let mut nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut nonce);
+        let payload = Payload {
+            msg: &private_key.to_bytes(),
+            aad: address.as_bytes(),
+        };
+        let ciphertext = Aes256Gcm::new(&key.into())
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|e| KeystoreError::Invalid(e.to_string()))?;
+
+        Ok(Keystore {
+            version: VERSION,
+            address,
+            scheme: private_key.scheme(),
+            crypto: Crypto {
+                cipher: CIPHER.to_string(),
+                ciphertext: hex::encode(ciphertext),
+                nonce: hex::encode(nonce),
+                kdf: KDF.to_string(),
+                kdfparams,
+            },
+        })
+    }
+
+    pub fn decrypt(&self, password: &str) -> Result<PrivateKey, KeystoreError> {
+        if self.version != VERSION {
+            return Err(KeystoreError::UnsupportedVersion(self.version));
+        }
+        if self.crypto.cipher != CIPHER {
+            return Err(KeystoreError::UnsupportedCipher(self.crypto.cipher.clone()));
+        }
+        if self.crypto.kdf != KDF {
+            return Err(KeystoreError::UnsupportedKdf(self.crypto.kdf.clone()));
+        }
+        let ciphertext = hex::decode(&self.crypto.ciphertext)
+            .map_err(|e| KeystoreError::Invalid(e.to_string()))?;
+        let nonce =
+            hex::decode(&self.crypto.nonce).map_err(|e| KeystoreError::Invalid(e.to_string()))?;
+        if nonce.len() != 12 {
+            return Err(KeystoreError::Invalid("nonce length".into()));
+        }
+
+        let key = self.crypto.kdfparams.derive_key(password)?;
+        // an edited address fails like a wrong password
+        let payload = Payload {
+            msg: &ciphertext,
+            aad: self.address.as_bytes(),
+        };
+        let private_key = Aes256Gcm::new(&key.into())
+            .decrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|_| KeystoreError::WrongPassword)?;
+        let private_key = PrivateKey::from_bytes(self.scheme, &private_key)
+            .map_err(|e| KeystoreError::Invalid(e.to_string()))?;
+        // an edited scheme reads the bytes as another key
+        if private_key.public_key().address() != self.address {
+            return Err(KeystoreError::Invalid(
+                "the key is not of the address".into(),
+            ));
+        }
+        Ok(private_key)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Keystore, KeystoreError> {
+        let file = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&file)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), KeystoreError> {
+        write_secret(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// write a file only its owner can read, replacing its content and its permissions if it exists
+pub fn write_secret(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        let file = options.open(path)?;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        (&file).write_all(contents.as_ref())
+    }
+    #[cfg(not(unix))]
+    {
+        options.open(path)?.write_all(contents.as_ref())
+    }
+}
+
+/// read the keystore password from the file, or prompt for it on the terminal
+/// a trailing newline in the file is not part of the password
+pub fn read_password(file: Option<&Path>, prompt: &str) -> Result<String, KeystoreError> {
+    match file {
+        Some(file) => {
+            let password = std::fs::read_to_string(file)?;
+            Ok(password.trim_end_matches(['\r', '\n']).to_string())
+        }
+        None => Ok(rpassword::prompt_password(prompt)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let private = "MIGEAgEAMBAGByqGSM49AgEGBSuBBAAKBG0wawIBAQQgYp6GnxdjxLvnucsaaTZ+J+FqtCdjbEaQsEqxk3KHJ3yhRANCAAR6X+Ws+hYmkOMIZTq/HMVBRbMcT1lADpd4z5c3MG6LzyuMDBMGOZ4C3gceN6I0/kzgQ/DWEZcNY4s6/WgLxUD1";
+        let address = "crt1az98ashxv3pa5x68yrz6ve8vz0ylaey28ccmy7";
+
+        let private_key = PrivateKey::parse(private).unwrap();
+        let keystore =
+            Keystore::encrypt(&private_key, "password", KdfParams::with_cost(4, 8, 1)).unwrap();
+        let keystore: Keystore =
+            serde_json::from_str(&serde_json::to_string(&keystore).unwrap()).unwrap();
+        assert_eq!(keystore.address, address);
+        assert_eq!(
+            keystore.decrypt("password").unwrap().encode().unwrap(),
+            private
+        );
+        assert!(matches!(
+            keystore.decrypt("wrong"),
+            Err(KeystoreError::WrongPassword)
+        ));
+        let mut edited: Keystore =
+            serde_json::from_str(&serde_json::to_string(&keystore).unwrap()).unwrap();
+        edited.address = "crt1rda9nkk7dv63muvhry040whcfaed5ccygdyzfr".into();
+        assert!(matches!(
+            edited.decrypt("password"),
+            Err(KeystoreError::WrongPassword)
+        ));
+
+        let path = std::env::temp_dir().join(format!("keystore-{}.json", std::process::id()));
+        keystore.save(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        assert_eq!(Keystore::load(&path).unwrap().address, address);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_schemes() {
+        for scheme in [Scheme::Ecdsa, Scheme::Schnorr, Scheme::Ed25519] {
+            let private_key = PrivateKey::generate(scheme);
+            let keystore =
+                Keystore::encrypt(&private_key, "password", KdfParams::with_cost(4, 8, 1)).unwrap();
+            let json = serde_json::to_string(&keystore).unwrap();
+            assert!(json.contains(&format!("\"scheme\":\"{}\"", scheme)));
+            let mut keystore: Keystore = serde_json::from_str(&json).unwrap();
+            let decrypted = keystore.decrypt("password").unwrap();
+            assert_eq!(decrypted.encode().unwrap(), private_key.encode().unwrap());
+
+            // the same bytes under another scheme are another key
+            keystore.scheme = match scheme {
+                Scheme::Ed25519 => Scheme::Schnorr,
+                _ => Scheme::Ed25519,
+            };
+            assert!(matches!(
+                keystore.decrypt("password"),
+                Err(KeystoreError::Invalid(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,335 @@
+use crate::error::CryptoError;
+use crate::{SigningMode, Utils};
+use k256::ecdsa::signature::{RandomizedSigner, Signer, Verifier};
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// a signature scheme, the tag carried by keys and signatures
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheme {
+    /// ECDSA over secp256k1, keys in base64 SPKI / PKCS#8
+    #[default]
+    Ecdsa,
+    /// BIP-340 Schnorr over secp256k1, x-only keys
+    Schnorr,
+    /// Ed25519
+    Ed25519,
+}
+
+impl Scheme {
+    /// the prefix of keys in text, none for ECDSA
+    fn prefix(&self) -> &'static str {
+        match self {
+            Scheme::Ecdsa => "",
+            Scheme::Schnorr => "schnorr:",
+            Scheme::Ed25519 => "ed25519:",
+        }
+    }
+
+    /// the scheme byte ahead of the key in `PublicKey::to_bytes`
+    fn tag(&self) -> u8 {
+        match self {
+            Scheme::Ecdsa => 0,
+            Scheme::Schnorr => 1,
+            Scheme::Ed25519 => 2,
+        }
+    }
+
+    /// split `schnorr:<hex>` and `ed25519:<hex>`, anything else is ECDSA
+    fn split(key: &str) -> (Scheme, &str) {
+        for scheme in [Scheme::Schnorr, Scheme::Ed25519] {
+            if let Some(key) = key.strip_prefix(scheme.prefix()) {
+                return (scheme, key);
+            }
+        }
+        (Scheme::Ecdsa, key)
+    }
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scheme::Ecdsa => write!(f, "ecdsa"),
+            Scheme::Schnorr => write!(f, "schnorr"),
+            Scheme::Ed25519 => write!(f, "ed25519"),
+        }
+    }
+}
+
+impl FromStr for Scheme {
+    type Err = CryptoError;
+
+    fn from_str(scheme: &str) -> Result<Scheme, CryptoError> {
+        match scheme {
+            "ecdsa" => Ok(Scheme::Ecdsa),
+            "schnorr" => Ok(Scheme::Schnorr),
+            "ed25519" => Ok(Scheme::Ed25519),
+            _ => Err(CryptoError::UnknownScheme(scheme.to_string())),
+        }
+    }
+}
+
+/// a public key of any scheme
+/// in text and JSON: base64 SPKI for ECDSA, `schnorr:<hex>` or `ed25519:<hex>` otherwise
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum PublicKey {
+    Ecdsa(k256::ecdsa::VerifyingKey),
+    Schnorr(k256::schnorr::VerifyingKey),
+    Ed25519(ed25519_dalek::VerifyingKey),
+}
+
+impl PublicKey {
+    pub fn parse(key: &str) -> Result<PublicKey, CryptoError> {
+        let invalid = |e: &dyn fmt::Display| CryptoError::InvalidPublicKey(e.to_string());
+        match Scheme::split(key) {
+            (Scheme::Ecdsa, key) => Ok(PublicKey::Ecdsa(Utils::get_verifying_key(key)?)),
+            (Scheme::Schnorr, key) => k256::schnorr::VerifyingKey::from_bytes(&hex::decode(key)?)
+                .map(PublicKey::Schnorr)
+                .map_err(|e| invalid(&e)),
+            (Scheme::Ed25519, key) => {
+                let bytes: [u8; 32] = hex::decode(key)?
+                    .try_into()
+                    .map_err(|_| invalid(&"expected 32 bytes"))?;
+                ed25519_dalek::VerifyingKey::from_bytes(&bytes)
+                    .map(PublicKey::Ed25519)
+                    .map_err(|e| invalid(&e))
+            }
+        }
+    }
+
+    pub fn scheme(&self) -> Scheme {
+        match self {
+            PublicKey::Ecdsa(_) => Scheme::Ecdsa,
+            PublicKey::Schnorr(_) => Scheme::Schnorr,
+            PublicKey::Ed25519(_) => Scheme::Ed25519,
+        }
+    }
+
+    /// the compressed point for ECDSA, the scheme byte and the key otherwise
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let key = match self {
+            PublicKey::Ecdsa(key) => return key.to_encoded_point(true).as_bytes().to_vec(),
+            PublicKey::Schnorr(key) => key.to_bytes().to_vec(),
+            PublicKey::Ed25519(key) => key.to_bytes().to_vec(),
+        };
+        let mut bytes = vec![self.scheme().tag()];
+        bytes.extend_from_slice(&key);
+        bytes
+    }
+
+    /// the account address of the key, see `Utils::address`
+    pub fn address(&self) -> String {
+        match self {
+            PublicKey::Ecdsa(key) => Utils::address(key),
+            _ => Utils::address_of(&self.to_bytes()),
+        }
+    }
+
+    /// the signature must be of the scheme of the key
+    pub fn verify(&self, data: &str, signature: &Signature) -> bool {
+        match (self, signature) {
+            (PublicKey::Ecdsa(key), Signature::Ecdsa(signature)) => {
+                Utils::verify_signature(data, signature, key)
+            }
+            (PublicKey::Schnorr(key), Signature::Schnorr(signature)) => {
+                key.verify(data.as_bytes(), signature).is_ok()
+            }
+            (PublicKey::Ed25519(key), Signature::Ed25519(signature)) => {
+                key.verify_strict(data.as_bytes(), signature).is_ok()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublicKey::Ecdsa(key) => {
+                let key = Utils::encode_verifying_key(key).map_err(|_| fmt::Error)?;
+                write!(f, "{}", key)
+            }
+            PublicKey::Schnorr(key) => write!(f, "schnorr:{}", hex::encode(key.to_bytes())),
+            PublicKey::Ed25519(key) => write!(f, "ed25519:{}", hex::encode(key.to_bytes())),
+        }
+    }
+}
+
+impl From<PublicKey> for String {
+    fn from(key: PublicKey) -> String {
+        key.to_string()
+    }
+}
+
+impl TryFrom<String> for PublicKey {
+    type Error = CryptoError;
+
+    fn try_from(key: String) -> Result<PublicKey, CryptoError> {
+        PublicKey::parse(&key)
+    }
+}
+
+/// a private key of any scheme
+/// in text: base64 PKCS#8 for ECDSA, `schnorr:<hex>` or `ed25519:<hex>` otherwise
+#[derive(Clone)]
+pub enum PrivateKey {
+    Ecdsa(k256::ecdsa::SigningKey),
+    Schnorr(k256::schnorr::SigningKey),
+    Ed25519(ed25519_dalek::SigningKey),
+}
+
+impl PrivateKey {
+    pub fn generate(scheme: Scheme) -> PrivateKey {
+        match scheme {
+            Scheme::Ecdsa => PrivateKey::Ecdsa(k256::ecdsa::SigningKey::random(&mut OsRng)),
+            Scheme::Schnorr => PrivateKey::Schnorr(k256::schnorr::SigningKey::random(&mut OsRng)),
+            Scheme::Ed25519 => PrivateKey::Ed25519(ed25519_dalek::SigningKey::generate(&mut OsRng)),
+        }
+    }
+
+    pub fn parse(key: &str) -> Result<PrivateKey, CryptoError> {
+        match Scheme::split(key) {
+            (Scheme::Ecdsa, key) => Ok(PrivateKey::Ecdsa(Utils::get_signing_key(key)?)),
+            (scheme, key) => PrivateKey::from_bytes(scheme, &hex::decode(key)?),
+        }
+    }
+
+    /// the 32 byte secret of any scheme, as `to_bytes` returns it
+    pub fn from_bytes(scheme: Scheme, bytes: &[u8]) -> Result<PrivateKey, CryptoError> {
+        let invalid = |e: &dyn fmt::Display| CryptoError::InvalidPrivateKey(e.to_string());
+        match scheme {
+            Scheme::Ecdsa => k256::ecdsa::SigningKey::from_slice(bytes)
+                .map(PrivateKey::Ecdsa)
+                .map_err(|e| invalid(&e)),
+            Scheme::Schnorr => k256::schnorr::SigningKey::from_bytes(bytes)
+                .map(PrivateKey::Schnorr)
+                .map_err(|e| invalid(&e)),
+            Scheme::Ed25519 => {
+                let bytes: [u8; 32] = bytes
+                    .try_into()
+                    .map_err(|_| invalid(&"expected 32 bytes"))?;
+                Ok(PrivateKey::Ed25519(ed25519_dalek::SigningKey::from_bytes(
+                    &bytes,
+                )))
+            }
+        }
+    }
+
+    /// the 32 byte secret, the scheme is not part of it
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            PrivateKey::Ecdsa(key) => key.to_bytes().to_vec(),
+            PrivateKey::Schnorr(key) => key.to_bytes().to_vec(),
+            PrivateKey::Ed25519(key) => key.to_bytes().to_vec(),
+        }
+    }
+
+    pub fn scheme(&self) -> Scheme {
+        self.public_key().scheme()
+    }
+
+    /// the key in the text format `parse` reads
+    pub fn encode(&self) -> Result<String, CryptoError> {
+        match self {
+            PrivateKey::Ecdsa(key) => Utils::encode_signing_key(key),
+            PrivateKey::Schnorr(key) => Ok(format!("schnorr:{}", hex::encode(key.to_bytes()))),
+            PrivateKey::Ed25519(key) => Ok(format!("ed25519:{}", hex::encode(key.to_bytes()))),
+        }
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        match self {
+            PrivateKey::Ecdsa(key) => PublicKey::Ecdsa(*key.verifying_key()),
+            PrivateKey::Schnorr(key) => PublicKey::Schnorr(*key.verifying_key()),
+            PrivateKey::Ed25519(key) => PublicKey::Ed25519(key.verifying_key()),
+        }
+    }
+
+    /// Ed25519 signatures are always deterministic
+    pub fn sign(&self, data: &str, mode: SigningMode) -> Signature {
+        match (self, mode) {
+            (PrivateKey::Ecdsa(key), mode) => {
+                Signature::Ecdsa(Utils::sign_data_with(data, key, mode))
+            }
+            (PrivateKey::Schnorr(key), SigningMode::Randomized) => {
+                Signature::Schnorr(key.sign_with_rng(&mut OsRng, data.as_bytes()))
+            }
+            (PrivateKey::Schnorr(key), SigningMode::Deterministic) => {
+                Signature::Schnorr(Signer::sign(key, data.as_bytes()))
+            }
+            (PrivateKey::Ed25519(key), _) => Signature::Ed25519(key.sign(data.as_bytes())),
+        }
+    }
+}
+
+/// a signature of any scheme, hex encoded: DER for ECDSA, 64 bytes otherwise
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Signature {
+    Ecdsa(k256::ecdsa::Signature),
+    Schnorr(k256::schnorr::Signature),
+    Ed25519(ed25519_dalek::Signature),
+}
+
+impl Signature {
+    /// the signature of a `scheme` key, see `Utils::decode_signature` for ECDSA
+    pub fn decode(scheme: Scheme, signature: &str) -> Result<Signature, CryptoError> {
+        let invalid = |e: &dyn fmt::Display| CryptoError::InvalidSignature(e.to_string());
+        match scheme {
+            Scheme::Ecdsa => Ok(Signature::Ecdsa(Utils::decode_signature(signature)?)),
+            Scheme::Schnorr => {
+                k256::schnorr::Signature::try_from(hex::decode(signature)?.as_slice())
+                    .map(Signature::Schnorr)
+                    .map_err(|e| invalid(&e))
+            }
+            Scheme::Ed25519 => ed25519_dalek::Signature::from_slice(&hex::decode(signature)?)
+                .map(Signature::Ed25519)
+                .map_err(|e| invalid(&e)),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        match self {
+            Signature::Ecdsa(signature) => Utils::encode_signature(signature),
+            Signature::Schnorr(signature) => hex::encode(signature.to_bytes()),
+            Signature::Ed25519(signature) => hex::encode(signature.to_bytes()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test() {
+        for scheme in [Scheme::Ecdsa, Scheme::Schnorr, Scheme::Ed25519] {
+            let private = PrivateKey::generate(scheme);
+            let private = PrivateKey::parse(&private.encode().unwrap()).unwrap();
+            let bytes = private.to_bytes();
+            let decoded = PrivateKey::from_bytes(scheme, &bytes).unwrap();
+            assert_eq!(decoded.public_key(), private.public_key());
+            let public = private.public_key();
+            assert_eq!(public.scheme(), scheme);
+            assert_eq!(PublicKey::parse(&public.to_string()).unwrap(), public);
+            assert!(Utils::decode_address(&public.address()).is_ok());
+
+            let signature = private.sign("data", SigningMode::Deterministic);
+            assert_eq!(signature, private.sign("data", SigningMode::Deterministic));
+            let signature = Signature::decode(scheme, &signature.encode()).unwrap();
+            assert!(public.verify("data", &signature));
+            assert!(!public.verify("other data", &signature));
+            let randomized = private.sign("data", SigningMode::Randomized);
+            assert!(public.verify("data", &randomized));
+        }
+
+        // a signature of another scheme never verifies
+        let schnorr = PrivateKey::generate(Scheme::Schnorr);
+        let ed25519 = PrivateKey::generate(Scheme::Ed25519);
+        let signature = ed25519.sign("data", SigningMode::Deterministic);
+        assert!(!schnorr.public_key().verify("data", &signature));
+    }
+}
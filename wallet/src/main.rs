@@ -0,0 +1,898 @@
+use clap::{Args, Parser, Subcommand};
+use jsonrpsee::core::client::ClientT;
+use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
+use jsonrpsee::rpc_params;
+use ledger::asset::AssetOperation;
+use ledger::contract::{contract_address, ContractOperation};
+use ledger::error::LedgerError;
+use ledger::governance::GovernanceOperation;
+use ledger::htlc::HtlcOperation;
+use ledger::multisig::Multisig;
+use ledger::staking::{Equivocation, StakingOperation};
+use ledger::transaction::{Lock, Memo, Output, Transaction};
+use std::fs;
+use std::path::PathBuf;
+use utils::hd;
+use utils::keystore::{self, KdfParams, Keystore};
+use utils::scheme::{PrivateKey, Scheme};
+use utils::{SigningMode, Utils};
+
+#[derive(Parser)]
+#[command(about = "Generate keys, sign and submit transfers")]
+struct Cli {
+    /// The node JSON-RPC endpoint
+    #[arg(long, env = "RPC_URL", default_value = "http://127.0.0.1:8545")]
+    rpc: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a key pair
+    Keygen {
+        /// The signature scheme: ecdsa, schnorr or ed25519
+        #[arg(long, default_value_t = Scheme::Ecdsa)]
+        scheme: Scheme,
+        /// Write the private key to an encrypted keystore file instead of printing it
+        #[arg(long)]
+        keystore: Option<PathBuf>,
+        /// Read the keystore password from a file instead of prompting
+        #[arg(long)]
+        password_file: Option<PathBuf>,
+    },
+    /// Generate a BIP-39 mnemonic to derive accounts from
+    Mnemonic,
+    /// Derive key pairs from a mnemonic
+    Derive {
+        #[arg(long, env = "MNEMONIC", hide_env_values = true)]
+        mnemonic: String,
+        /// The optional BIP-39 passphrase
+        #[arg(
+            long,
+            env = "MNEMONIC_PASSPHRASE",
+            default_value = "",
+            hide_env_values = true
+        )]
+        passphrase: String,
+        /// The BIP-32 derivation path
+        #[arg(long, default_value = hd::DEFAULT_PATH, conflicts_with = "count")]
+        path: String,
+        /// List the addresses of the first accounts instead, m/44'/6517364'/0'/0/{0..count}
+        #[arg(long)]
+        count: Option<u32>,
+    },
+    /// Show the balance of an account
+    Balance {
+        address: String,
+        /// The id of an asset, instead of the native coin
+        #[arg(long)]
+        asset: Option<String>,
+    },
+    /// Sign a transfer and submit it to the node
+    Transfer {
+        #[command(flatten)]
+        key: KeySource,
+        /// The receiver address
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        amount: u64,
+        /// Defaults to the next nonce of the sender known to the node
+        #[arg(long)]
+        nonce: Option<u64>,
+        #[command(flatten)]
+        options: TransferOptions,
+    },
+    /// Sign a transfer to several receivers under one nonce and submit it to the node
+    Batch {
+        #[command(flatten)]
+        key: KeySource,
+        /// A receiver and its amount, `ADDRESS:AMOUNT`, repeated for each receiver
+        #[arg(long = "pay", required = true, value_parser = parse_output)]
+        outputs: Vec<Output>,
+        /// Defaults to the next nonce of the sender known to the node
+        #[arg(long)]
+        nonce: Option<u64>,
+        #[command(flatten)]
+        options: TransferOptions,
+    },
+    /// Issue an asset, its id is the printed transaction hash
+    Issue {
+        #[command(flatten)]
+        key: KeySource,
+        /// 1 to 12 uppercase letters and digits
+        #[arg(long)]
+        symbol: String,
+        #[arg(long, default_value_t = 0)]
+        decimals: u8,
+        /// Defaults to the next nonce of the sender known to the node
+        #[arg(long)]
+        nonce: Option<u64>,
+    },
+    /// Mint units of an asset to its issuer
+    Mint {
+        #[command(flatten)]
+        key: KeySource,
+        /// The asset id
+        #[arg(long)]
+        asset: String,
+        #[arg(long)]
+        amount: u64,
+        /// Defaults to the next nonce of the sender known to the node
+        #[arg(long)]
+        nonce: Option<u64>,
+    },
+    /// Burn units of an asset held by its issuer
+    Burn {
+        #[command(flatten)]
+        key: KeySource,
+        /// The asset id
+        #[arg(long)]
+        asset: String,
+        #[arg(long)]
+        amount: u64,
+        /// Defaults to the next nonce of the sender known to the node
+        #[arg(long)]
+        nonce: Option<u64>,
+    },
+    /// Show an asset
+    Asset { id: String },
+    /// Lock an amount to a receiver until a deadline, its id is the printed transaction hash
+    HtlcOpen {
+        #[command(flatten)]
+        key: KeySource,
+        /// The receiver address
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        amount: u64,
+        /// The hex SHA-256 of the secret the receiver claims with
+        #[arg(long, required_unless_present = "secret", conflicts_with = "secret")]
+        hashlock: Option<String>,
+        /// The secret, hashed into the hashlock
+        #[arg(long)]
+        secret: Option<String>,
+        /// The receiver may claim before, and the sender refund from, `height:N` or `time:N`
+        #[arg(long)]
+        deadline: Lock,
+        /// The id of the asset to lock, instead of the native coin
+        #[arg(long)]
+        asset: Option<String>,
+        /// Defaults to the next nonce of the sender known to the node
+        #[arg(long)]
+        nonce: Option<u64>,
+    },
+    /// Claim a contract locked to the key with its secret
+    HtlcClaim {
+        #[command(flatten)]
+        key: KeySource,
+        /// The contract id
+        #[arg(long)]
+        htlc: String,
+        #[arg(long)]
+        preimage: String,
+        /// Defaults to the next nonce of the sender known to the node
+        #[arg(long)]
+        nonce: Option<u64>,
+    },
+    /// Refund a contract opened by the key once past its deadline
+    HtlcRefund {
+        #[command(flatten)]
+        key: KeySource,
+        /// The contract id
+        #[arg(long)]
+        htlc: String,
+        /// Defaults to the next nonce of the sender known to the node
+        #[arg(long)]
+        nonce: Option<u64>,
+    },
+    /// Show a hashed time-locked contract
+    Htlc { id: String },
+    /// Deploy a WebAssembly smart contract, prints the transaction hash and the contract address
+    Deploy {
+        #[command(flatten)]
+        key: KeySource,
+        /// The compiled module
+        #[arg(long)]
+        code: PathBuf,
+        /// Defaults to the next nonce of the sender known to the node
+        #[arg(long)]
+        nonce: Option<u64>,
+    },
+    /// Call a smart contract
+    Call {
+        #[command(flatten)]
+        key: KeySource,
+        /// The contract address
+        #[arg(long)]
+        contract: String,
+        /// The call input, hex
+        #[arg(long, default_value = "")]
+        input: String,
+        /// Paid to the contract before the call
+        #[arg(long, default_value_t = 0)]
+        amount: u64,
+        /// The most gas the call may use, the gas used is paid from the sender
+        #[arg(long, default_value_t = 1_000_000)]
+        gas: u64,
+        /// Defaults to the next nonce of the sender known to the node
+        #[arg(long)]
+        nonce: Option<u64>,
+    },
+    /// Show the gas used and the output of a smart contract call
+    Receipt { hash: String },
+    /// Show the hex value of a hex key in the storage of a smart contract
+    Storage { contract: String, key: String },
+    /// Bond native coins of the key as validator stake
+    Bond {
+        #[command(flatten)]
+        key: KeySource,
+        #[arg(long)]
+        amount: u64,
+        /// Defaults to the next nonce of the sender known to the node
+        #[arg(long)]
+        nonce: Option<u64>,
+    },
+    /// Start unbonding stake of the key, prints the unbonding id
+    Unbond {
+        #[command(flatten)]
+        key: KeySource,
+        #[arg(long)]
+        amount: u64,
+        /// Defaults to the next nonce of the sender known to the node
+        #[arg(long)]
+        nonce: Option<u64>,
+    },
+    /// Withdraw a released unbonding of the key
+    Withdraw {
+        #[command(flatten)]
+        key: KeySource,
+        /// The unbonding id
+        #[arg(long)]
+        unbonding: String,
+        /// Defaults to the next nonce of the sender known to the node
+        #[arg(long)]
+        nonce: Option<u64>,
+    },
+    /// Slash a producer for signing two blocks at one height
+    Slash {
+        #[command(flatten)]
+        key: KeySource,
+        /// The two block headers, JSON as in the equivocation event
+        #[arg(long)]
+        evidence: PathBuf,
+        /// Defaults to the next nonce of the sender known to the node
+        #[arg(long)]
+        nonce: Option<u64>,
+    },
+    /// Show the bonded stake and the unbondings of an address
+    Stake { address: String },
+    /// Freeze an account, with the governance key
+    Freeze {
+        #[command(flatten)]
+        key: KeySource,
+        #[arg(long)]
+        address: String,
+        /// Defaults to the next nonce of the sender known to the node
+        #[arg(long)]
+        nonce: Option<u64>,
+    },
+    /// Unfreeze an account, with the governance key
+    Unfreeze {
+        #[command(flatten)]
+        key: KeySource,
+        #[arg(long)]
+        address: String,
+        /// Defaults to the next nonce of the sender known to the node
+        #[arg(long)]
+        nonce: Option<u64>,
+    },
+    /// Set the daily spending limit of an account, with the governance key
+    Limit {
+        #[command(flatten)]
+        key: KeySource,
+        #[arg(long)]
+        address: String,
+        /// In native coins, no limit if omitted
+        #[arg(long)]
+        limit: Option<u64>,
+        /// Defaults to the next nonce of the sender known to the node
+        #[arg(long)]
+        nonce: Option<u64>,
+    },
+    /// Show whether an account is frozen and its daily spending limit
+    Control { address: String },
+    /// Show the validators of an epoch, the current one by default
+    Validators {
+        #[arg(long)]
+        epoch: Option<u64>,
+    },
+    /// Show the address of an M-of-N multisig account
+    MultisigAddress {
+        #[command(flatten)]
+        policy: Policy,
+    },
+    /// Write an unsigned transfer from a multisig account to a file, for its holders to `sign`
+    MultisigTransfer {
+        #[command(flatten)]
+        policy: Policy,
+        /// The receiver address
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        amount: u64,
+        /// Defaults to the next nonce of the multisig account known to the node
+        #[arg(long)]
+        nonce: Option<u64>,
+        #[command(flatten)]
+        options: TransferOptions,
+        /// The transaction file
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Add a signature to a transaction file
+    Sign {
+        #[command(flatten)]
+        key: KeySource,
+        /// The transaction file, rewritten in place
+        file: PathBuf,
+    },
+    /// Submit a signed transaction file to the node
+    Submit { file: PathBuf },
+}
+
+/// the asset, validity window, `height:N` or `time:N` (unix seconds), and memo of a transfer
+#[derive(Args)]
+struct TransferOptions {
+    /// The id of the asset to transfer, instead of the native coin
+    #[arg(long)]
+    asset: Option<String>,
+    /// The transfer is not valid before this point
+    #[arg(long)]
+    valid_after: Option<Lock>,
+    /// The transfer is not valid after this point
+    #[arg(long)]
+    valid_until: Option<Lock>,
+    /// A text memo, e.g. an invoice reference
+    #[arg(long, conflicts_with = "memo_hex")]
+    memo: Option<String>,
+    /// A binary memo, hex
+    #[arg(long)]
+    memo_hex: Option<String>,
+}
+
+impl TransferOptions {
+    fn memo(&self) -> Result<Option<Memo>, Box<dyn std::error::Error>> {
+        Ok(match (&self.memo, &self.memo_hex) {
+            (Some(text), _) => Some(Memo::Text(text.clone())),
+            (None, Some(data)) => Some(Memo::Binary(hex::decode(data)?)),
+            (None, None) => None,
+        })
+    }
+
+    fn apply(&self, transaction: &mut Transaction) -> Result<(), Box<dyn std::error::Error>> {
+        transaction.asset_set(self.asset.clone())?;
+        transaction.validity_set(self.valid_after, self.valid_until)?;
+        transaction.memo_set(self.memo()?)?;
+        Ok(())
+    }
+}
+
+/// the keys and threshold of a multisig account, the address depends on the key order
+#[derive(Args)]
+struct Policy {
+    /// The number of signatures needed
+    #[arg(long)]
+    threshold: usize,
+    /// The public keys of the holders, in order, of any scheme
+    #[arg(long = "key", required = true)]
+    keys: Vec<String>,
+}
+
+impl Policy {
+    fn multisig(&self) -> Result<Multisig, Box<dyn std::error::Error>> {
+        let keys: Vec<&str> = self.keys.iter().map(String::as_str).collect();
+        Ok(Multisig::new(self.threshold, &keys)?)
+    }
+}
+
+/// where the signing key comes from, exactly one of `key`, `keystore` or `mnemonic`
+#[derive(Args)]
+struct KeySource {
+    /// The sender private key, base64 PKCS#8, or `schnorr:<hex>` / `ed25519:<hex>`
+    #[arg(long, env = "KEY_PRIV", hide_env_values = true, group = "source")]
+    key: Option<String>,
+    /// The sender encrypted keystore file
+    #[arg(long, env = "KEYSTORE", group = "source")]
+    keystore: Option<PathBuf>,
+    /// Read the keystore password from a file instead of prompting
+    #[arg(long, requires = "keystore")]
+    password_file: Option<PathBuf>,
+    /// The mnemonic the sender key is derived from
+    #[arg(long, env = "MNEMONIC", hide_env_values = true, group = "source")]
+    mnemonic: Option<String>,
+    /// The optional BIP-39 passphrase
+    #[arg(
+        long,
+        env = "MNEMONIC_PASSPHRASE",
+        default_value = "",
+        hide_env_values = true
+    )]
+    passphrase: String,
+    /// The BIP-32 derivation path of the sender key
+    #[arg(long, default_value = hd::DEFAULT_PATH)]
+    path: String,
+}
+
+impl KeySource {
+    fn private_key(self) -> Result<PrivateKey, Box<dyn std::error::Error>> {
+        let key = if let Some(key) = self.key {
+            key
+        } else if let Some(path) = self.keystore {
+            let password =
+                keystore::read_password(self.password_file.as_deref(), "Keystore password: ")?;
+            return Ok(Keystore::load(path)?.decrypt(&password)?);
+        } else if let Some(mnemonic) = self.mnemonic {
+            hd::derive_keypair(&mnemonic, &self.passphrase, &self.path)?.0
+        } else {
+            return Err("one of --key, --keystore or --mnemonic is required".into());
+        };
+        Ok(PrivateKey::parse(&key)?)
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Keygen {
+            scheme,
+            keystore,
+            password_file,
+        } => {
+            let private_key = PrivateKey::generate(scheme);
+            let public = private_key.public_key().to_string();
+            let address = private_key.public_key().address();
+            match keystore {
+                Some(path) => {
+                    let password = new_password(password_file)?;
+                    Keystore::encrypt(&private_key, &password, KdfParams::new())?.save(&path)?;
+                    println!("Keystore written to {}", path.display());
+                }
+                None => {
+                    println!("Private Key:");
+                    println!("{}", private_key.encode()?);
+                }
+            }
+            println!("Public Key:");
+            println!("{}", public);
+            println!("Address:");
+            println!("{}", address);
+        }
+        Command::Mnemonic => {
+            println!("{}", hd::generate_mnemonic());
+        }
+        Command::Derive {
+            mnemonic,
+            passphrase,
+            path,
+            count,
+        } => match count {
+            Some(count) => {
+                for index in 0..count {
+                    let path = hd::account_path(index);
+                    let (_, public) = hd::derive_keypair(&mnemonic, &passphrase, &path)?;
+                    let address = Utils::address(&Utils::get_verifying_key(&public)?);
+                    println!("{} {}", path, address);
+                }
+            }
+            None => {
+                let (private, public) = hd::derive_keypair(&mnemonic, &passphrase, &path)?;
+                println!("Private Key (Base64 Encoded):");
+                println!("{}", private);
+                println!("Public Key (Base64 Encoded):");
+                println!("{}", public);
+                println!("Address:");
+                println!("{}", Utils::address(&Utils::get_verifying_key(&public)?));
+            }
+        },
+        Command::Balance { address, asset } => {
+            let client = client(&cli.rpc)?;
+            let balance: u64 = match asset {
+                Some(asset) => {
+                    client
+                        .request("getBalance", rpc_params![address, asset])
+                        .await?
+                }
+                None => client.request("getBalance", rpc_params![address]).await?,
+            };
+            println!("{}", balance);
+        }
+        Command::Transfer {
+            key,
+            to,
+            amount,
+            nonce,
+            options,
+        } => {
+            let client = client(&cli.rpc)?;
+            let private_key = key.private_key()?;
+            let sender_key = private_key.public_key();
+            let nonce = match nonce {
+                Some(nonce) => nonce,
+                None => next_nonce(&client, &sender_key.address()).await?,
+            };
+
+            let mut transaction =
+                Transaction::new(nonce, amount, &sender_key.to_string(), &to, None)?;
+            options.apply(&mut transaction)?;
+            transaction.sign_with_key(&private_key, SigningMode::Randomized)?;
+            let hash: String = client
+                .request("submitTransaction", rpc_params![transaction])
+                .await?;
+            println!("{}", hash);
+        }
+        Command::Batch {
+            key,
+            outputs,
+            nonce,
+            options,
+        } => {
+            let client = client(&cli.rpc)?;
+            let private_key = key.private_key()?;
+            let sender_key = private_key.public_key();
+            let nonce = match nonce {
+                Some(nonce) => nonce,
+                None => next_nonce(&client, &sender_key.address()).await?,
+            };
+
+            let mut transaction = Transaction::new_batch(nonce, &sender_key.to_string(), outputs)?;
+            options.apply(&mut transaction)?;
+            transaction.sign_with_key(&private_key, SigningMode::Randomized)?;
+            let hash: String = client
+                .request("submitTransaction", rpc_params![transaction])
+                .await?;
+            println!("{}", hash);
+        }
+        Command::Issue {
+            key,
+            symbol,
+            decimals,
+            nonce,
+        } => {
+            let operation = AssetOperation::Issue { symbol, decimals };
+            let hash = operation_submit(&cli.rpc, key, nonce, |nonce, sender| {
+                Transaction::new_asset(nonce, sender, operation)
+            });
+            println!("{}", hash.await?);
+        }
+        Command::Mint {
+            key,
+            asset,
+            amount,
+            nonce,
+        } => {
+            let operation = AssetOperation::Mint { asset, amount };
+            let hash = operation_submit(&cli.rpc, key, nonce, |nonce, sender| {
+                Transaction::new_asset(nonce, sender, operation)
+            });
+            println!("{}", hash.await?);
+        }
+        Command::Burn {
+            key,
+            asset,
+            amount,
+            nonce,
+        } => {
+            let operation = AssetOperation::Burn { asset, amount };
+            let hash = operation_submit(&cli.rpc, key, nonce, |nonce, sender| {
+                Transaction::new_asset(nonce, sender, operation)
+            });
+            println!("{}", hash.await?);
+        }
+        Command::Asset { id } => {
+            let client = client(&cli.rpc)?;
+            let asset: Option<serde_json::Value> =
+                client.request("getAsset", rpc_params![id]).await?;
+            match asset {
+                Some(asset) => println!("{}", serde_json::to_string_pretty(&asset)?),
+                None => return Err("unknown asset".into()),
+            }
+        }
+        Command::HtlcOpen {
+            key,
+            to,
+            amount,
+            hashlock,
+            secret,
+            deadline,
+            asset,
+            nonce,
+        } => {
+            let hashlock = match secret {
+                Some(secret) => Utils::hash_data(&secret),
+                None => hashlock.ok_or("missing hashlock")?,
+            };
+            let operation = HtlcOperation::Open {
+                receiver: to,
+                amount,
+                hashlock,
+                deadline,
+            };
+            let hash = operation_submit(&cli.rpc, key, nonce, |nonce, sender| {
+                let mut transaction = Transaction::new_htlc(nonce, sender, operation)?;
+                transaction.asset_set(asset)?;
+                Ok(transaction)
+            });
+            println!("{}", hash.await?);
+        }
+        Command::HtlcClaim {
+            key,
+            htlc,
+            preimage,
+            nonce,
+        } => {
+            let operation = HtlcOperation::Claim { htlc, preimage };
+            let hash = operation_submit(&cli.rpc, key, nonce, |nonce, sender| {
+                Transaction::new_htlc(nonce, sender, operation)
+            });
+            println!("{}", hash.await?);
+        }
+        Command::HtlcRefund { key, htlc, nonce } => {
+            let operation = HtlcOperation::Refund { htlc };
+            let hash = operation_submit(&cli.rpc, key, nonce, |nonce, sender| {
+                Transaction::new_htlc(nonce, sender, operation)
+            });
+            println!("{}", hash.await?);
+        }
+        Command::Htlc { id } => {
+            let client = client(&cli.rpc)?;
+            let htlc: Option<serde_json::Value> =
+                client.request("getHtlc", rpc_params![id]).await?;
+            match htlc {
+                Some(htlc) => println!("{}", serde_json::to_string_pretty(&htlc)?),
+                None => return Err("unknown htlc".into()),
+            }
+        }
+        Command::Deploy { key, code, nonce } => {
+            let operation = ContractOperation::Deploy {
+                code: fs::read(code)?,
+            };
+            let hash = operation_submit(&cli.rpc, key, nonce, |nonce, sender| {
+                Transaction::new_contract(nonce, sender, operation)
+            })
+            .await?;
+            println!("{}", hash);
+            println!("{}", contract_address(&hash));
+        }
+        Command::Call {
+            key,
+            contract,
+            input,
+            amount,
+            gas,
+            nonce,
+        } => {
+            let operation = ContractOperation::Call {
+                contract,
+                input: hex::decode(input)?,
+                amount,
+                gas,
+            };
+            let hash = operation_submit(&cli.rpc, key, nonce, |nonce, sender| {
+                Transaction::new_contract(nonce, sender, operation)
+            });
+            println!("{}", hash.await?);
+        }
+        Command::Receipt { hash } => {
+            let client = client(&cli.rpc)?;
+            let receipt: Option<serde_json::Value> =
+                client.request("getReceipt", rpc_params![hash]).await?;
+            match receipt {
+                Some(receipt) => println!("{}", serde_json::to_string_pretty(&receipt)?),
+                None => return Err("unknown receipt".into()),
+            }
+        }
+        Command::Storage { contract, key } => {
+            let client = client(&cli.rpc)?;
+            let value: Option<String> = client
+                .request("getStorage", rpc_params![contract, key])
+                .await?;
+            match value {
+                Some(value) => println!("{}", value),
+                None => return Err("no value".into()),
+            }
+        }
+        Command::Bond { key, amount, nonce } => {
+            let operation = StakingOperation::Bond { amount };
+            let hash = operation_submit(&cli.rpc, key, nonce, |nonce, sender| {
+                Transaction::new_staking(nonce, sender, operation)
+            });
+            println!("{}", hash.await?);
+        }
+        Command::Unbond { key, amount, nonce } => {
+            let operation = StakingOperation::Unbond { amount };
+            let hash = operation_submit(&cli.rpc, key, nonce, |nonce, sender| {
+                Transaction::new_staking(nonce, sender, operation)
+            });
+            println!("{}", hash.await?);
+        }
+        Command::Withdraw {
+            key,
+            unbonding,
+            nonce,
+        } => {
+            let operation = StakingOperation::Withdraw { unbonding };
+            let hash = operation_submit(&cli.rpc, key, nonce, |nonce, sender| {
+                Transaction::new_staking(nonce, sender, operation)
+            });
+            println!("{}", hash.await?);
+        }
+        Command::Slash {
+            key,
+            evidence,
+            nonce,
+        } => {
+            let evidence: Equivocation = serde_json::from_slice(&fs::read(evidence)?)?;
+            let operation = StakingOperation::Slash {
+                evidence: Box::new(evidence),
+            };
+            let hash = operation_submit(&cli.rpc, key, nonce, |nonce, sender| {
+                Transaction::new_staking(nonce, sender, operation)
+            });
+            println!("{}", hash.await?);
+        }
+        Command::Stake { address } => {
+            let client = client(&cli.rpc)?;
+            let stake: serde_json::Value = client.request("getStake", rpc_params![address]).await?;
+            println!("{}", serde_json::to_string_pretty(&stake)?);
+        }
+        Command::Freeze {
+            key,
+            address,
+            nonce,
+        } => {
+            let operation = GovernanceOperation::Freeze { address };
+            let hash = operation_submit(&cli.rpc, key, nonce, |nonce, sender| {
+                Transaction::new_governance(nonce, sender, operation)
+            });
+            println!("{}", hash.await?);
+        }
+        Command::Unfreeze {
+            key,
+            address,
+            nonce,
+        } => {
+            let operation = GovernanceOperation::Unfreeze { address };
+            let hash = operation_submit(&cli.rpc, key, nonce, |nonce, sender| {
+                Transaction::new_governance(nonce, sender, operation)
+            });
+            println!("{}", hash.await?);
+        }
+        Command::Limit {
+            key,
+            address,
+            limit,
+            nonce,
+        } => {
+            let operation = GovernanceOperation::Limit { address, limit };
+            let hash = operation_submit(&cli.rpc, key, nonce, |nonce, sender| {
+                Transaction::new_governance(nonce, sender, operation)
+            });
+            println!("{}", hash.await?);
+        }
+        Command::Control { address } => {
+            let client = client(&cli.rpc)?;
+            let control: serde_json::Value =
+                client.request("getControl", rpc_params![address]).await?;
+            println!("{}", serde_json::to_string_pretty(&control)?);
+        }
+        Command::Validators { epoch } => {
+            let client = client(&cli.rpc)?;
+            let validators: Option<serde_json::Value> = match epoch {
+                Some(epoch) => client.request("getValidators", rpc_params![epoch]).await?,
+                None => client.request("getValidators", rpc_params![]).await?,
+            };
+            match validators {
+                Some(validators) => println!("{}", serde_json::to_string_pretty(&validators)?),
+                None => return Err("epoch not reached".into()),
+            }
+        }
+        Command::MultisigAddress { policy } => {
+            println!("{}", policy.multisig()?.address());
+        }
+        Command::MultisigTransfer {
+            policy,
+            to,
+            amount,
+            nonce,
+            options,
+            out,
+        } => {
+            let multisig = policy.multisig()?;
+            let nonce = match nonce {
+                Some(nonce) => nonce,
+                None => next_nonce(&client(&cli.rpc)?, &multisig.address()).await?,
+            };
+            let mut transaction = Transaction::new_multisig(nonce, amount, multisig, &to)?;
+            options.apply(&mut transaction)?;
+            fs::write(&out, serde_json::to_string_pretty(&transaction)?)?;
+            println!("{}", transaction.hash());
+        }
+        Command::Sign { key, file } => {
+            let private_key = key.private_key()?;
+            let mut transaction: Transaction = serde_json::from_str(&fs::read_to_string(&file)?)?;
+            transaction.sign_with_key(&private_key, SigningMode::Randomized)?;
+            fs::write(&file, serde_json::to_string_pretty(&transaction)?)?;
+        }
+        Command::Submit { file } => {
+            let client = client(&cli.rpc)?;
+            let transaction: Transaction = serde_json::from_str(&fs::read_to_string(&file)?)?;
+            let hash: String = client
+                .request("submitTransaction", rpc_params![transaction])
+                .await?;
+            println!("{}", hash);
+        }
+    }
+
+    Ok(())
+}
+
+/// the password for a new keystore, prompted twice unless read from a file
+fn new_password(file: Option<PathBuf>) -> Result<String, Box<dyn std::error::Error>> {
+    if file.is_some() {
+        return Ok(keystore::read_password(file.as_deref(), "")?);
+    }
+    let password = keystore::read_password(None, "New keystore password: ")?;
+    if password != keystore::read_password(None, "Repeat password: ")? {
+        return Err("passwords do not match".into());
+    }
+    Ok(password)
+}
+
+/// build an operation from the nonce and sender key, sign and submit it, returns the transaction hash
+async fn operation_submit(
+    rpc: &str,
+    key: KeySource,
+    nonce: Option<u64>,
+    build: impl FnOnce(u64, &str) -> Result<Transaction, LedgerError>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let client = client(rpc)?;
+    let private_key = key.private_key()?;
+    let sender_key = private_key.public_key();
+    let nonce = match nonce {
+        Some(nonce) => nonce,
+        None => next_nonce(&client, &sender_key.address()).await?,
+    };
+
+    let mut transaction = build(nonce, &sender_key.to_string())?;
+    transaction.sign_with_key(&private_key, SigningMode::Randomized)?;
+    Ok(client
+        .request("submitTransaction", rpc_params![transaction])
+        .await?)
+}
+
+/// the nonce after the last one of the address known to the node
+async fn next_nonce(client: &HttpClient, address: &str) -> Result<u64, Box<dyn std::error::Error>> {
+    let nonce: u64 = client.request("getNonce", rpc_params![address]).await?;
+    Ok(nonce + 1)
+}
+
+fn parse_output(output: &str) -> Result<Output, String> {
+    let (receiver, amount) = output
+        .rsplit_once(':')
+        .ok_or_else(|| format!("expected ADDRESS:AMOUNT, got {}", output))?;
+    let amount = amount
+        .parse()
+        .map_err(|_| format!("invalid amount {}", amount))?;
+    Ok(Output {
+        receiver: receiver.to_string(),
+        amount,
+    })
+}
+
+fn client(url: &str) -> Result<HttpClient, Box<dyn std::error::Error>> {
+    Ok(HttpClientBuilder::default().build(url)?)
+}